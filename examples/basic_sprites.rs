@@ -16,19 +16,30 @@ fn main() {
     let mut render_buddy = block_on(RenderBuddy::new(&window, (1280, 720))).unwrap();
     let img = image::load_from_memory(include_bytes!("./assets/bitbuddy.png")).unwrap();
     let dimensions = (img.width(), img.height());
-    let handle = render_buddy.add_texture(Image {
-        data: img.into_bytes(),
-        dimensions,
-        ..Default::default()
-    });
+    let handle = render_buddy
+        .add_texture(Image {
+            data: img.into_bytes(),
+            dimensions,
+            ..Default::default()
+        })
+        .unwrap();
 
     let camera = Camera::orthographic();
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::MainEventsCleared => {
-            let mut render_ctx = render_buddy.begin();
+            let mut render_ctx = match render_buddy.begin() {
+                Ok(render_ctx) => render_ctx,
+                Err(e) if e.should_skip_frame() => return,
+                Err(e) => panic!("{}", e),
+            };
             render_buddy.push(Sprite::new(handle), Vec3::ZERO);
-            render_buddy.render(&mut render_ctx, Some(Vec4::new(0.2, 0.2, 0.8, 1.)), &camera);
+            render_buddy.render(
+                &mut render_ctx,
+                Some(Vec4::new(0.2, 0.2, 0.8, 1.)),
+                &camera,
+                false,
+            );
             render_buddy.end_frame(render_ctx);
         }
         Event::RedrawEventsCleared => *control_flow = ControlFlow::Poll,
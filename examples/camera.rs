@@ -26,7 +26,11 @@ fn main() {
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::MainEventsCleared => {
-            let mut render_ctx = render_buddy.begin();
+            let mut render_ctx = match render_buddy.begin() {
+                Ok(render_ctx) => render_ctx,
+                Err(e) if e.should_skip_frame() => return,
+                Err(e) => panic!("{}", e),
+            };
             render_buddy.push(
                 Rect::new(Vec2::new(50., 50.), Vec4::new(0.9, 0.2, 0.2, 1.)),
                 Vec3::ZERO,
@@ -35,6 +39,7 @@ fn main() {
                 &mut render_ctx,
                 Some(Vec4::new(0.2, 0.2, 0.8, 1.)),
                 &default_camera,
+                false,
             );
 
             render_buddy.push(
@@ -42,7 +47,7 @@ fn main() {
                     .with_anchor(Anchor::TopLeft),
                 Vec3::ZERO,
             );
-            render_buddy.render(&mut render_ctx, None, &top_left_camera);
+            render_buddy.render(&mut render_ctx, None, &top_left_camera, false);
 
             render_buddy.end_frame(render_ctx);
         }
@@ -0,0 +1,40 @@
+use glam::{Vec3, Vec4};
+use pollster::block_on;
+use render_buddy::{camera::Camera, sprite::Sprite, texture::Image, wgpu, RenderBuddy};
+
+fn main() {
+    let mut render_buddy = block_on(RenderBuddy::new_headless(
+        (640, 360),
+        wgpu::TextureFormat::Rgba8UnormSrgb,
+    ))
+    .unwrap();
+    let img = image::load_from_memory(include_bytes!("./assets/bitbuddy.png")).unwrap();
+    let dimensions = (img.width(), img.height());
+    let handle = render_buddy
+        .add_texture(Image {
+            data: img.into_bytes(),
+            dimensions,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let mut render_ctx = render_buddy.begin().unwrap();
+    render_buddy.push(Sprite::new(handle), Vec3::ZERO);
+    render_buddy.render(
+        &mut render_ctx,
+        Some(Vec4::new(0.2, 0.2, 0.8, 1.)),
+        &Camera::orthographic(),
+        false,
+    );
+    let frame = render_buddy.capture_frame(&mut render_ctx).unwrap();
+    render_buddy.end_frame(render_ctx);
+
+    image::save_buffer(
+        "headless.png",
+        &frame.data,
+        frame.dimensions.0,
+        frame.dimensions.1,
+        image::ColorType::Rgba8,
+    )
+    .unwrap();
+}
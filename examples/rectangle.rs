@@ -17,7 +17,11 @@ fn main() {
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::MainEventsCleared => {
-            let mut render_ctx = render_buddy.begin();
+            let mut render_ctx = match render_buddy.begin() {
+                Ok(render_ctx) => render_ctx,
+                Err(e) if e.should_skip_frame() => return,
+                Err(e) => panic!("{}", e),
+            };
             render_buddy.push(
                 Rect::new(Vec2::new(100., 100.), Vec4::new(0.5, 0.5, 0.5, 1.)),
                 Vec3::ZERO,
@@ -38,6 +42,7 @@ fn main() {
                 &mut render_ctx,
                 Some(Vec4::new(0.2, 0.2, 0.8, 1.)),
                 &Camera::orthographic(),
+                false,
             );
             render_buddy.end_frame(render_ctx);
         }
@@ -17,12 +17,17 @@ fn main() {
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::MainEventsCleared => {
-            let mut render_ctx = render_buddy.begin();
+            let mut render_ctx = match render_buddy.begin() {
+                Ok(render_ctx) => render_ctx,
+                Err(e) if e.should_skip_frame() => return,
+                Err(e) => panic!("{}", e),
+            };
             render_buddy.append(Text::new("Hello, Render Buddy!", 32.), Vec3::ZERO);
             render_buddy.render(
                 &mut render_ctx,
                 Some(Vec4::new(0.2, 0.2, 0.8, 1.)),
                 &Camera::orthographic(),
+                false,
             );
             render_buddy.end_frame(render_ctx);
         }
@@ -1127,7 +1127,7 @@ impl<T> FromIterator<T> for Arena<T> {
     #[inline]
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
         let mut arena = Arena::new();
-        arena.extend(iter.into_iter());
+        arena.extend(iter);
         arena
     }
 }
@@ -1271,10 +1271,7 @@ impl<T> Handle<T> {
 
 impl<T> Clone for Handle<T> {
     fn clone(&self) -> Handle<T> {
-        Handle {
-            id: self.id,
-            marker: PhantomData,
-        }
+        *self
     }
 }
 
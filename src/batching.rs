@@ -1,114 +1,257 @@
+use std::{ops::Range, sync::Arc};
+
 use crate::{
     arena::{ArenaId, Handle},
+    bind_groups::BindGroupLayoutBuilder,
     mesh::Mesh,
-    pipeline::Pipeline,
+    pipeline::{Pipeline, PipelineKey},
+    rect::Rect,
+    stencil::{has_stencil, StencilMode},
+    texture::Texture,
     RenderBuddy,
 };
-use wgpu::{util::DeviceExt, BindGroup, Buffer};
+use wgpu::{
+    util::align_to, BindGroup, BindGroupLayout, BindingType, BufferAddress, Device, ShaderStages,
+};
+
+/// Offsets into the vertex and index buffers are aligned to this, which fits every index format
+const BUFFER_ALIGNMENT: BufferAddress = 4;
 
 #[derive(Debug)]
 pub(crate) struct PreparedMeshBatch {
-    pub(crate) vertex_buffer: Buffer,
-    pub(crate) index_buffer: Buffer,
+    /// Byte range of the batch in the frame's vertex buffer
+    pub(crate) vertex_range: Range<BufferAddress>,
+    /// Byte range of the batch in the frame's index buffer
+    pub(crate) index_range: Range<BufferAddress>,
     pub(crate) vert_len: u32,
     pub(crate) indices_len: u32,
     pub(crate) material_handle: Handle<Pipeline>,
+    pub(crate) clip: Option<Rect>,
+    pub(crate) stencil_mode: StencilMode,
+    pub(crate) stencil_reference: u32,
+    pub(crate) texture_bind_group: Option<Arc<BindGroup>>,
+    /// Bind groups provided by the material, bound after the texture
     pub(crate) bind_groups: Vec<BindGroup>,
 }
 
+/// Texture bind groups are cached per texture, sampler and sample type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct TextureBindGroupKey {
+    pub(crate) texture: ArenaId,
+    pub(crate) sampler: ArenaId,
+    pub(crate) filterable: bool,
+}
+
+/// The texture bind group layouts shared by every material,
+/// so cached texture bind groups work with any pipeline
+#[derive(Debug)]
+pub(crate) struct TextureBindGroupLayouts {
+    pub(crate) filterable: BindGroupLayout,
+    pub(crate) non_filterable: BindGroupLayout,
+}
+
+impl TextureBindGroupLayouts {
+    pub(crate) fn new(device: &Device) -> Self {
+        Self {
+            filterable: create_texture_bind_group_layout(device, true),
+            non_filterable: create_texture_bind_group_layout(device, false),
+        }
+    }
+}
+
+fn create_texture_bind_group_layout(device: &Device, filterable: bool) -> BindGroupLayout {
+    BindGroupLayoutBuilder::new()
+        .append(
+            ShaderStages::FRAGMENT,
+            BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+            },
+            None,
+        )
+        .append(
+            ShaderStages::FRAGMENT,
+            BindingType::Sampler(if filterable {
+                wgpu::SamplerBindingType::Filtering
+            } else {
+                wgpu::SamplerBindingType::NonFiltering
+            }),
+            None,
+        )
+        .build(device, Some("texture_bind_group_layout"))
+}
+
 impl RenderBuddy {
-    pub(crate) fn prepare_mesh_batch(&mut self) -> Vec<PreparedMeshBatch> {
+    pub(crate) fn prepare_mesh_batch(&mut self, key: PipelineKey) -> Vec<PreparedMeshBatch> {
         let mut meshes = self.meshes.drain(0..).collect::<Vec<Mesh>>();
 
         meshes.sort_by(|a, b| a.z.partial_cmp(&b.z).unwrap_or(std::cmp::Ordering::Equal));
 
         let mut current_batch_texture_handle = Handle::new(ArenaId::default());
         let mut current_material_handle_id = ArenaId::default();
+        let mut current_clip = None;
+        let mut current_stencil = (StencilMode::Disabled, 0);
         let mut batches: Vec<Mesh> = Vec::new();
 
-        for mesh in meshes {
+        // Without a stencil attachment masks are dropped and masked meshes drawn normally
+        let stencil_enabled = key.depth_format.is_some_and(has_stencil);
+        for mut mesh in meshes {
+            if !stencil_enabled {
+                if mesh.stencil_mode == StencilMode::Mask {
+                    continue;
+                }
+                mesh.stencil_mode = StencilMode::Disabled;
+            }
+
+            let clip = mesh.clip.map(|clip| (clip.min, clip.max));
+            let stencil = (mesh.stencil_mode, mesh.stencil_reference);
             if current_batch_texture_handle == mesh.texture_handle.unwrap_or(Handle::default())
                 && current_material_handle_id == mesh.material_handle.id
+                && current_clip == clip
+                && current_stencil == stencil
+                && !batches.is_empty()
             {
                 let length = batches.len();
 
                 let current_mesh = &mut batches[length - 1];
-                let vert_count = current_mesh.vertices.len() as u16;
+                let vert_count = current_mesh.vertices.len() as u32;
                 let indices = mesh
                     .indices
                     .iter()
                     .map(|index| index + vert_count)
-                    .collect::<Vec<u16>>();
+                    .collect::<Vec<u32>>();
 
                 current_mesh.concat(mesh.vertices, indices);
             } else {
                 current_batch_texture_handle = mesh.texture_handle.unwrap_or(Handle::default());
                 current_material_handle_id = mesh.material_handle.id;
+                current_clip = clip;
+                current_stencil = stencil;
                 batches.push(mesh);
             }
         }
 
-        batches
+        self.prepare_pipelines(batches.iter().map(|batch| {
+            (
+                batch.material_handle,
+                PipelineKey {
+                    stencil_mode: batch.stencil_mode,
+                    ..key
+                },
+            )
+        }));
+
+        let vertex_bytes = batches
             .iter()
             .map(|batch| {
-                let vertex_buffer =
-                    self.device
-                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some("Vertex Buffer"),
-                            contents: &batch
-                                .vertices
-                                .iter()
-                                .map(|v| v.get_bytes())
-                                .flatten()
-                                .collect::<Vec<u8>>(),
-                            usage: wgpu::BufferUsages::VERTEX,
-                        });
-
-                let index_buffer =
-                    self.device
-                        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                            label: Some("Index Buffer"),
-                            contents: bytemuck::cast_slice(&batch.indices),
-                            usage: wgpu::BufferUsages::INDEX,
-                        });
-
-                let mut bind_groups = Vec::default();
-
-                let material = self
+                batch
+                    .vertices
+                    .iter()
+                    .flat_map(|v| v.get_bytes())
+                    .collect::<Vec<u8>>()
+            })
+            .collect::<Vec<Vec<u8>>>();
+
+        // Reserve the whole pass up front so every batch ends up in the same buffer
+        self.vertex_buffer.reserve(
+            &self.device,
+            vertex_bytes
+                .iter()
+                .map(|bytes| align_to(bytes.len() as BufferAddress, BUFFER_ALIGNMENT))
+                .sum(),
+        );
+        self.index_buffer.reserve(
+            &self.device,
+            batches
+                .iter()
+                .map(|batch| {
+                    align_to(
+                        std::mem::size_of_val(batch.indices.as_slice()) as BufferAddress,
+                        BUFFER_ALIGNMENT,
+                    )
+                })
+                .sum(),
+        );
+
+        batches
+            .iter()
+            .zip(vertex_bytes)
+            .map(|(batch, vertex_bytes)| {
+                let vertex_offset =
+                    self.vertex_buffer
+                        .write(&self.queue, &vertex_bytes, BUFFER_ALIGNMENT);
+                let index_bytes: &[u8] = bytemuck::cast_slice(&batch.indices);
+                let index_offset =
+                    self.index_buffer
+                        .write(&self.queue, index_bytes, BUFFER_ALIGNMENT);
+
+                let material = &self
                     .materials
                     .get(batch.material_handle)
-                    .expect("Cant find material for batch");
-
-                if let Some(texture_handle) = batch.texture_handle {
-                    if material.material.has_texture() {
-                        let texture = self.textures.get(texture_handle).unwrap();
-                        let sampler = self.samplers.get(texture.sampler).unwrap();
-                        let texture_bind_group = texture.create_bind_group(
-                            &self.device,
-                            &material.render_pipeline.get_bind_group_layout(1),
-                            &sampler,
-                        );
-
-                        bind_groups.push(texture_bind_group);
-                    }
-                }
+                    .expect("Cant find material for batch")
+                    .material;
+                let filterable = material.filterable_texture();
 
-                let mut mat_bind_groups =
-                    material
-                        .material
-                        .get_bind_groups(&batch, &self, &material.render_pipeline);
+                let texture_bind_group = match batch.texture_handle {
+                    Some(texture_handle) if material.has_texture() => {
+                        Some(self.get_texture_bind_group(texture_handle, filterable))
+                    }
+                    _ => None,
+                };
 
-                bind_groups.append(&mut mat_bind_groups);
+                let material = self.materials.get(batch.material_handle).unwrap();
+                let bind_groups = material.material.get_bind_groups(
+                    batch,
+                    self,
+                    material.render_pipeline(PipelineKey {
+                        stencil_mode: batch.stencil_mode,
+                        ..key
+                    }),
+                );
 
                 PreparedMeshBatch {
-                    vertex_buffer,
-                    index_buffer,
+                    vertex_range: vertex_offset
+                        ..vertex_offset + vertex_bytes.len() as BufferAddress,
+                    index_range: index_offset..index_offset + index_bytes.len() as BufferAddress,
+                    texture_bind_group,
                     bind_groups,
                     vert_len: batch.vertices.len() as _,
                     indices_len: batch.indices.len() as _,
                     material_handle: batch.material_handle,
+                    clip: batch.clip,
+                    stencil_mode: batch.stencil_mode,
+                    stencil_reference: batch.stencil_reference,
                 }
             })
             .collect()
     }
+
+    /// Returns the cached bind group for the texture and its sampler, creating it on first use
+    fn get_texture_bind_group(
+        &mut self,
+        texture_handle: Handle<Texture>,
+        filterable: bool,
+    ) -> Arc<BindGroup> {
+        let texture = self.textures.get(texture_handle).unwrap();
+        let key = TextureBindGroupKey {
+            texture: texture_handle.id,
+            sampler: texture.sampler.id,
+            filterable,
+        };
+
+        self.texture_bind_groups
+            .entry(key)
+            .or_insert_with(|| {
+                let sampler = self.samplers.get(texture.sampler).unwrap();
+                let bind_group_layout = if filterable {
+                    &self.texture_bind_group_layouts.filterable
+                } else {
+                    &self.texture_bind_group_layouts.non_filterable
+                };
+
+                Arc::new(texture.create_bind_group(&self.device, bind_group_layout, sampler))
+            })
+            .clone()
+    }
 }
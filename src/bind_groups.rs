@@ -12,6 +12,12 @@ use wgpu::{
 pub struct BindGroupLayoutBuilder {
     bgl_entries: Vec<BindGroupLayoutEntry>,
 }
+impl Default for BindGroupLayoutBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BindGroupLayoutBuilder {
     pub fn new() -> Self {
         Self {
@@ -65,6 +71,12 @@ pub struct BindGroupBuilder<'a> {
     bg_entries: Vec<BindGroupEntry<'a>>,
 }
 
+impl<'a> Default for BindGroupBuilder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> BindGroupBuilder<'a> {
     pub fn new() -> Self {
         Self {
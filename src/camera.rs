@@ -1,5 +1,4 @@
 use glam::{Mat4, Quat, Vec2, Vec3};
-use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, Device};
 
 pub const DEFAULT_ORTHO_CAMERA_DEPTH: f32 = 1000.0;
 
@@ -103,24 +102,19 @@ impl Camera {
         }
     }
 
-    pub(crate) fn create_bind_group(
-        &self,
-        device: &Device,
-        viewport_size: (u32, u32),
-        bind_group_layout: &BindGroupLayout,
-    ) -> BindGroup {
+    pub(crate) fn uniform(&self, viewport_size: (u32, u32)) -> CameraUniform {
         let projection = self.compute_projection_matrix(viewport_size);
 
-        let additive = if let Projection::Orthographic { origin, .. } = self.projection {
-            if let CameraOrigin::TopLeft = origin {
-                Vec3::new(
-                    viewport_size.0 as f32 / 2.,
-                    -(viewport_size.1 as f32) / 2.,
-                    0.,
-                )
-            } else {
-                Vec3::ZERO
-            }
+        let additive = if let Projection::Orthographic {
+            origin: CameraOrigin::TopLeft,
+            ..
+        } = self.projection
+        {
+            Vec3::new(
+                viewport_size.0 as f32 / 2.,
+                -(viewport_size.1 as f32) / 2.,
+                0.,
+            )
         } else {
             Vec3::ZERO
         };
@@ -133,24 +127,9 @@ impl Camera {
         let inverse_view = view.inverse();
         let view_projection = projection * inverse_view;
 
-        let camera_uniform = CameraUniform {
+        CameraUniform {
             view_proj: view_projection.to_cols_array_2d(),
-        };
-
-        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Camera Buffer"),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-            contents: bytemuck::cast_slice(&[camera_uniform]),
-        });
-
-        device.create_bind_group(&wgpu::BindGroupDescriptor {
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
-            label: Some("Camera bind group"),
-            layout: &bind_group_layout,
-        })
+        }
     }
 
     pub(crate) fn compute_projection_matrix(&self, viewport_size: (u32, u32)) -> Mat4 {
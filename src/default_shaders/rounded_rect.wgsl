@@ -0,0 +1,73 @@
+struct View {
+    view_proj: mat4x4<f32>
+};
+@group(0) @binding(0)
+var<uniform> view: View;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    // xy: position relative to the center of the rect, zw: half the size of the rect
+    @location(3) local: vec4<f32>,
+    // top left, top right, bottom right, bottom left
+    @location(4) corner_radii: vec4<f32>,
+    @location(5) border_color: vec4<f32>,
+    // x: border width
+    @location(6) border: vec4<f32>
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) local: vec4<f32>,
+    @location(2) corner_radii: vec4<f32>,
+    @location(3) border_color: vec4<f32>,
+    @location(4) border: vec4<f32>
+};
+
+@vertex
+fn vertex(
+    obj_vert: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * vec4<f32>(obj_vert.position, 1.0);
+    out.color = obj_vert.color;
+    out.local = obj_vert.local;
+    out.corner_radii = obj_vert.corner_radii;
+    out.border_color = obj_vert.border_color;
+    out.border = obj_vert.border;
+    return out;
+}
+
+// Distance to the edge of the rounded rect, negative inside
+fn rounded_rect(point: vec2<f32>, half_size: vec2<f32>, corner_radii: vec4<f32>) -> f32 {
+    let left_radius = select(corner_radii.w, corner_radii.x, point.y > 0.0);
+    let right_radius = select(corner_radii.z, corner_radii.y, point.y > 0.0);
+    let radius = select(left_radius, right_radius, point.x > 0.0);
+    let corner = abs(point) - half_size + radius;
+
+    return min(max(corner.x, corner.y), 0.0) + length(max(corner, vec2<f32>(0.0))) - radius;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = rounded_rect(in.local.xy, in.local.zw, in.corner_radii);
+    // Fade over a pixel so edges are anti aliased at any scale
+    let pixel_size = max(fwidth(distance), 0.0001);
+    let coverage = clamp(0.5 - distance / pixel_size, 0.0, 1.0);
+
+    var color = in.color;
+    if in.border.x > 0.0 {
+        let fill = clamp(0.5 - (distance + in.border.x) / pixel_size, 0.0, 1.0);
+        color = mix(in.border_color, in.color, fill);
+    }
+    color.a *= coverage;
+
+    // Keeps the rounded corners out of stencil masks
+    if color.a <= 0.0 {
+        discard;
+    }
+
+    return color;
+}
@@ -0,0 +1,85 @@
+struct View {
+    view_proj: mat4x4<f32>
+};
+@group(0) @binding(0)
+var<uniform> view: View;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    // x: outline width, y: shadow softness, in distance field units, zw: shadow offset in uvs
+    @location(3) params: vec4<f32>,
+    @location(4) outline_color: vec4<f32>,
+    @location(5) shadow_color: vec4<f32>,
+    // Corners of the glyph in the atlas, the shadow isn't sampled outside of them
+    @location(6) uv_rect: vec4<f32>
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) params: vec4<f32>,
+    @location(3) outline_color: vec4<f32>,
+    @location(4) shadow_color: vec4<f32>,
+    @location(5) uv_rect: vec4<f32>
+};
+
+@vertex
+fn vertex(
+    obj_vert: VertexInput
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * vec4<f32>(obj_vert.position, 1.0);
+    out.uv = obj_vert.uv;
+    out.color = obj_vert.color;
+    out.params = obj_vert.params;
+    out.outline_color = obj_vert.outline_color;
+    out.shadow_color = obj_vert.shadow_color;
+    out.uv_rect = obj_vert.uv_rect;
+    return out;
+}
+
+@group(1) @binding(0)
+var obj_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var obj_sampler: sampler;
+
+// How much of a pixel is inside an edge, the distance is positive inside
+fn coverage(distance: f32, edge_width: f32) -> f32 {
+    return clamp(distance / edge_width + 0.5, 0.0, 1.0);
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = textureSample(obj_texture, obj_sampler, in.uv).a - 0.5;
+    // Fade over a pixel so edges are anti aliased at any scale
+    let pixel_size = max(fwidth(distance), 0.0001);
+    let outline_width = in.params.x;
+
+    var glyph = mix(in.outline_color, in.color, coverage(distance, pixel_size));
+    glyph.a *= coverage(distance + outline_width, pixel_size);
+
+    let shadow_uv = in.uv - in.params.zw;
+    let in_glyph = all(shadow_uv >= in.uv_rect.xy) && all(shadow_uv <= in.uv_rect.zw);
+    let shadow_distance = textureSample(
+        obj_texture,
+        obj_sampler,
+        clamp(shadow_uv, in.uv_rect.xy, in.uv_rect.zw)
+    ).a - 0.5;
+    let shadow = select(
+        0.0,
+        coverage(shadow_distance + outline_width, max(in.params.y, pixel_size)),
+        in_glyph
+    ) * in.shadow_color.a;
+
+    // The glyph is drawn over its shadow
+    let alpha = glyph.a + shadow * (1.0 - glyph.a);
+    if alpha <= 0.0 {
+        discard;
+    }
+    let color = (glyph.rgb * glyph.a + in.shadow_color.rgb * shadow * (1.0 - glyph.a)) / alpha;
+
+    return vec4<f32>(color, alpha);
+}
@@ -0,0 +1,153 @@
+use wgpu::{
+    Backends, CompositeAlphaMode, Features, Limits, PowerPreference, PresentMode, TextureFormat,
+};
+
+/// Configures the device and surface a [`RenderBuddy`](crate::RenderBuddy) is created with
+///
+/// ```
+/// use render_buddy::{descriptor::RenderBuddyDescriptor, wgpu};
+///
+/// let descriptor = RenderBuddyDescriptor::new()
+///     .with_power_preference(wgpu::PowerPreference::HighPerformance)
+///     .with_present_mode(wgpu::PresentMode::AutoNoVsync)
+///     .with_alpha_mode(wgpu::CompositeAlphaMode::PreMultiplied);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RenderBuddyDescriptor {
+    pub backends: Backends,
+    pub power_preference: PowerPreference,
+    pub features: Features,
+    /// Defaults to the WebGL 2 limits on wasm and the wgpu defaults everywhere else
+    pub limits: Option<Limits>,
+    /// Falls back to [`PresentMode::Fifo`] if the surface doesn't support it,
+    /// defaults to the surface's preferred mode
+    pub present_mode: Option<PresentMode>,
+    /// Falls back to the surface's preferred mode if the surface doesn't support it,
+    /// use [`CompositeAlphaMode::PreMultiplied`] or [`CompositeAlphaMode::PostMultiplied`] for transparent windows
+    pub alpha_mode: Option<CompositeAlphaMode>,
+    /// Picks an sRGB surface format when true, a linear one otherwise
+    pub srgb: bool,
+    /// MSAA sample count for rendering to the surface, 1 disables MSAA
+    /// Creating a [`RenderBuddy`](crate::RenderBuddy) fails if the surface or depth format doesn't support it,
+    /// 4 is supported for most formats on devices that aren't downlevel
+    pub sample_count: u32,
+    /// Adds a stencil buffer to the depth textures, required for [`StencilMode`](crate::stencil::StencilMode) masks
+    pub stencil: bool,
+    /// Lets [`RenderBuddy::capture_frame`](crate::RenderBuddy::capture_frame) read back the surface from the start,
+    /// see [`RenderBuddy::set_frame_capture`](crate::RenderBuddy::set_frame_capture)
+    pub frame_capture: bool,
+    /// Bytes of glyph atlases kept between frames, the least recently drawn font sizes are freed past it
+    pub glyph_cache_budget: usize,
+}
+
+impl Default for RenderBuddyDescriptor {
+    fn default() -> Self {
+        Self {
+            backends: Backends::all(),
+            power_preference: PowerPreference::default(),
+            features: Features::empty(),
+            limits: None,
+            present_mode: None,
+            alpha_mode: None,
+            srgb: true,
+            sample_count: 1,
+            stencil: false,
+            frame_capture: false,
+            glyph_cache_budget: 64 << 20,
+        }
+    }
+}
+
+impl RenderBuddyDescriptor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_backends(mut self, backends: Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference: PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn with_features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// Use [`PresentMode::AutoVsync`] or [`PresentMode::AutoNoVsync`] to toggle vsync on any platform
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = Some(present_mode);
+        self
+    }
+
+    pub fn with_alpha_mode(mut self, alpha_mode: CompositeAlphaMode) -> Self {
+        self.alpha_mode = Some(alpha_mode);
+        self
+    }
+
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    pub fn with_stencil(mut self, stencil: bool) -> Self {
+        self.stencil = stencil;
+        self
+    }
+
+    pub fn with_frame_capture(mut self, frame_capture: bool) -> Self {
+        self.frame_capture = frame_capture;
+        self
+    }
+
+    pub fn with_glyph_cache_budget(mut self, glyph_cache_budget: usize) -> Self {
+        self.glyph_cache_budget = glyph_cache_budget;
+        self
+    }
+
+    pub(crate) fn limits(&self) -> Limits {
+        self.limits.clone().unwrap_or_else(|| {
+            // Webgl 2 for web until WGPU is fully supported
+            if cfg!(target_arch = "wasm32") {
+                Limits::downlevel_webgl2_defaults()
+            } else {
+                Limits::default()
+            }
+        })
+    }
+
+    pub(crate) fn depth_format(&self) -> TextureFormat {
+        if self.stencil {
+            TextureFormat::Depth24PlusStencil8
+        } else {
+            TextureFormat::Depth32Float
+        }
+    }
+}
+
+/// Picks the requested present mode if the surface supports it
+/// The auto modes are always supported as wgpu resolves them itself
+pub(crate) fn select_present_mode(
+    requested: PresentMode,
+    supported_present_modes: &[PresentMode],
+) -> PresentMode {
+    match requested {
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync => requested,
+        _ if supported_present_modes.contains(&requested) => requested,
+        _ => PresentMode::Fifo,
+    }
+}
@@ -0,0 +1,97 @@
+use wgpu::{util::align_to, Buffer, BufferAddress, BufferUsages, Device, Queue};
+
+/// A GPU buffer that is filled linearly during a frame with [`Queue::write_buffer`]
+/// and reused every frame instead of allocating new buffers
+pub(crate) struct DynamicBuffer {
+    buffer: Buffer,
+    label: &'static str,
+    usage: BufferUsages,
+    capacity: BufferAddress,
+    len: BufferAddress,
+}
+
+impl DynamicBuffer {
+    pub(crate) fn new(
+        device: &Device,
+        label: &'static str,
+        usage: BufferUsages,
+        capacity: BufferAddress,
+    ) -> Self {
+        let usage = usage | BufferUsages::COPY_DST;
+
+        Self {
+            buffer: create_buffer(device, label, usage, capacity),
+            label,
+            usage,
+            capacity,
+            len: 0,
+        }
+    }
+
+    pub(crate) fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// Makes sure `size` more bytes can be written, replacing the buffer with a larger one if they don't fit
+    /// Returns true if the buffer was replaced, anything already recorded keeps using the old buffer
+    pub(crate) fn reserve(&mut self, device: &Device, size: BufferAddress) -> bool {
+        if self.len + size <= self.capacity {
+            return false;
+        }
+
+        self.capacity = size.next_power_of_two().max(self.capacity * 2);
+        self.buffer = create_buffer(device, self.label, self.usage, self.capacity);
+        self.len = 0;
+
+        true
+    }
+
+    /// Writes the bytes after everything written so far this frame and returns their offset
+    /// The next write starts at the given alignment, which has to be a multiple of 4
+    pub(crate) fn write(
+        &mut self,
+        queue: &Queue,
+        bytes: &[u8],
+        alignment: BufferAddress,
+    ) -> BufferAddress {
+        let offset = self.len;
+        let size = bytes.len() as BufferAddress;
+        debug_assert!(
+            offset + size <= self.capacity,
+            "Write exceeds reserved space"
+        );
+
+        // Writes have to be a multiple of 4 bytes
+        let padding = align_to(size, wgpu::COPY_BUFFER_ALIGNMENT) - size;
+        if padding == 0 {
+            queue.write_buffer(&self.buffer, offset, bytes);
+        } else {
+            let mut padded_bytes = bytes.to_vec();
+            padded_bytes.resize((size + padding) as usize, 0);
+            queue.write_buffer(&self.buffer, offset, &padded_bytes);
+        }
+
+        self.len = align_to(offset + size, alignment);
+
+        offset
+    }
+
+    /// Starts writing from the beginning again, should only be called once the frame is submitted
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+fn create_buffer(
+    device: &Device,
+    label: &'static str,
+    usage: BufferUsages,
+    capacity: BufferAddress,
+) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: capacity,
+        usage,
+        mapped_at_creation: false,
+    })
+}
@@ -2,13 +2,17 @@ use crate::texture_atlas::TextureAtlas;
 use crate::{rect::Rect, texture::Image};
 
 use glam::{IVec2, Vec2};
-use guillotiere::{size2, Allocation, AtlasAllocator};
+use guillotiere::{size2, Allocation, AtlasAllocator, Rectangle};
 
 pub struct DynamicTextureAtlasBuilder {
     pub atlas_allocator: AtlasAllocator,
     pub padding: u32,
     pub(crate) temp_image_data: Image,
     pub texture_atlas: TextureAtlas,
+    /// Allocation of each texture in the atlas, `None` once it's removed
+    allocations: Vec<Option<Allocation>>,
+    /// Area of the image changed since the last [`DynamicTextureAtlasBuilder::take_dirty_rect`]
+    dirty_rect: Option<Rectangle>,
 }
 
 impl DynamicTextureAtlasBuilder {
@@ -19,10 +23,12 @@ impl DynamicTextureAtlasBuilder {
             padding,
             temp_image_data,
             texture_atlas,
+            allocations: Vec::new(),
+            dirty_rect: None,
         }
     }
 
-    pub(crate) fn add_texture(&mut self, texture_data_to_add: &Image) -> Option<(usize, Image)> {
+    pub(crate) fn add_texture(&mut self, texture_data_to_add: &Image) -> Option<usize> {
         let allocation = self.atlas_allocator.allocate(size2(
             (texture_data_to_add.dimensions.0 + self.padding) as i32,
             (texture_data_to_add.dimensions.1 + self.padding) as i32,
@@ -30,17 +36,69 @@ impl DynamicTextureAtlasBuilder {
 
         if let Some(allocation) = allocation {
             self.place_texture(allocation, texture_data_to_add);
+            self.allocations.push(Some(allocation));
+            self.mark_dirty(allocation.rectangle);
             let mut rect: Rect = to_rect(allocation.rectangle);
             rect.max -= self.padding as f32;
-            Some((
-                self.texture_atlas.add_texture(rect),
-                self.temp_image_data.clone(),
-            ))
+            Some(self.texture_atlas.add_texture(rect))
         } else {
             None
         }
     }
 
+    /// Makes the atlas bigger, keeping the textures already in it where they are
+    pub(crate) fn grow(&mut self, size: Vec2) {
+        self.atlas_allocator.grow(to_size2(size));
+
+        let format_size: usize = self.temp_image_data.format.describe().block_size.into();
+        let old_width = self.temp_image_data.dimensions.0 as usize * format_size;
+        let new_width = size.x as usize * format_size;
+        let mut data = vec![0; new_width * size.y as usize];
+        for (old_row, new_row) in self
+            .temp_image_data
+            .data
+            .chunks_exact(old_width)
+            .zip(data.chunks_exact_mut(new_width))
+        {
+            new_row[..old_width].copy_from_slice(old_row);
+        }
+
+        self.temp_image_data.data = data;
+        self.temp_image_data.dimensions = (size.x as u32, size.y as u32);
+        self.texture_atlas.size = size;
+    }
+
+    /// Frees the space of a texture for new textures, its index isn't reused
+    pub(crate) fn remove_texture(&mut self, index: usize) {
+        let Some(allocation) = self.allocations.get_mut(index).and_then(Option::take) else {
+            return;
+        };
+        self.atlas_allocator.deallocate(allocation.id);
+
+        // Clear the texture so it doesn't bleed into textures placed next to its space
+        let rect = allocation.rectangle;
+        self.mark_dirty(rect);
+        let atlas_width = self.temp_image_data.dimensions.0 as usize;
+        let format_size: usize = self.temp_image_data.format.describe().block_size.into();
+        for y in rect.min.y as usize..rect.max.y as usize {
+            let begin = (y * atlas_width + rect.min.x as usize) * format_size;
+            let end = begin + rect.width() as usize * format_size;
+            self.temp_image_data.data[begin..end].fill(0);
+        }
+    }
+
+    /// Returns the area changed since the last call, only that area has to be written to the atlas texture
+    pub(crate) fn take_dirty_rect(&mut self) -> Option<Rect> {
+        self.dirty_rect.take().map(to_rect)
+    }
+
+    fn mark_dirty(&mut self, rectangle: Rectangle) {
+        self.dirty_rect = Some(match self.dirty_rect {
+            Some(dirty_rect) => dirty_rect.union(&rectangle),
+            None => rectangle,
+        });
+    }
+
     fn place_texture(&mut self, allocation: Allocation, texture_data_to_add: &Image) {
         let mut rect = allocation.rectangle;
         rect.max.x -= self.padding as i32;
@@ -1,30 +1,145 @@
-use wgpu::{CreateSurfaceError, RequestDeviceError};
+use std::fmt::Display;
+
+use wgpu::{BufferAsyncError, CreateSurfaceError, RequestDeviceError, SurfaceError, TextureFormat};
 
 #[derive(Debug)]
-pub struct RenderBuddyError {
-    pub message: String,
+pub enum RenderBuddyError {
+    /// No adapter matching the requested options could be found
+    AdapterNotFound,
+    /// The adapter was unable to provide a device
+    RequestDevice(RequestDeviceError),
+    /// A surface could not be created for the window
+    CreateSurface(CreateSurfaceError),
+    /// The surface was lost and has to be configured again
+    SurfaceLost,
+    /// The surface no longer matches the window and has to be configured again
+    SurfaceOutdated,
+    /// Acquiring the next surface texture took too long
+    SurfaceTimeout,
+    /// The viewport has no area, usually because the window is minimized
+    ZeroSizedViewport,
+    /// The GPU ran out of memory
+    OutOfMemory,
+    /// Frame captures weren't enabled with [`RenderBuddy::set_frame_capture`](crate::RenderBuddy::set_frame_capture)
+    /// or the surface texture can't be copied from on this backend
+    CaptureUnsupported,
+    /// Mapping a buffer for reading failed
+    BufferMap(BufferAsyncError),
+    /// The font data could not be parsed
+    FontParse(&'static str),
+    /// The texture is empty or larger than the device allows
+    InvalidTextureSize { width: u32, height: u32 },
+    /// The amount of bytes does not match the size and format of the texture
+    InvalidTextureData { expected: usize, actual: usize },
+    /// The texture format can't be uploaded and sampled from by the device
+    UnsupportedTextureFormat(TextureFormat),
+    /// The MSAA sample count isn't supported for the surface or depth format
+    UnsupportedSampleCount {
+        sample_count: u32,
+        format: TextureFormat,
+    },
+    /// The shader or pipeline of a material failed validation
+    Shader(String),
+    /// The handle does not point to a live resource
+    InvalidHandle(&'static str),
+}
+
+impl Display for RenderBuddyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RenderBuddyError::AdapterNotFound => write!(f, "Unable to request adapter from wgpu"),
+            RenderBuddyError::RequestDevice(e) => write!(f, "wgpu::RequestDeviceError {}", e),
+            RenderBuddyError::CreateSurface(e) => write!(f, "wgpu::CreateSurfaceError {}", e),
+            RenderBuddyError::SurfaceLost => write!(f, "The surface was lost"),
+            RenderBuddyError::SurfaceOutdated => write!(f, "The surface is outdated"),
+            RenderBuddyError::SurfaceTimeout => {
+                write!(f, "Timed out acquiring the next surface texture")
+            }
+            RenderBuddyError::ZeroSizedViewport => write!(f, "The viewport has a size of zero"),
+            RenderBuddyError::OutOfMemory => write!(f, "Out of GPU memory"),
+            RenderBuddyError::CaptureUnsupported => {
+                write!(
+                    f,
+                    "Frame captures were not enabled or are not supported by the surface on this backend"
+                )
+            }
+            RenderBuddyError::BufferMap(e) => write!(f, "wgpu::BufferAsyncError {}", e),
+            RenderBuddyError::FontParse(e) => write!(f, "Unable to parse font: {}", e),
+            RenderBuddyError::InvalidTextureSize { width, height } => {
+                write!(f, "Invalid texture size {}x{}", width, height)
+            }
+            RenderBuddyError::InvalidTextureData { expected, actual } => write!(
+                f,
+                "Invalid texture data, expected {} bytes but got {}",
+                expected, actual
+            ),
+            RenderBuddyError::UnsupportedTextureFormat(format) => {
+                write!(f, "Unsupported texture format {:?}", format)
+            }
+            RenderBuddyError::UnsupportedSampleCount {
+                sample_count,
+                format,
+            } => write!(
+                f,
+                "Sample count {} is not supported for {:?}",
+                sample_count, format
+            ),
+            RenderBuddyError::Shader(e) => write!(f, "Invalid material: {}", e),
+            RenderBuddyError::InvalidHandle(resource) => write!(f, "Invalid {} handle", resource),
+        }
+    }
 }
 
 impl RenderBuddyError {
-    pub fn new(message: impl ToString) -> Self {
-        RenderBuddyError {
-            message: message.to_string(),
+    /// Returns true for surface errors that only mean the current frame can't be drawn,
+    /// rendering can carry on with the next frame
+    pub fn should_skip_frame(&self) -> bool {
+        matches!(
+            self,
+            RenderBuddyError::SurfaceLost
+                | RenderBuddyError::SurfaceOutdated
+                | RenderBuddyError::SurfaceTimeout
+                | RenderBuddyError::ZeroSizedViewport
+        )
+    }
+}
+
+impl std::error::Error for RenderBuddyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderBuddyError::RequestDevice(e) => Some(e),
+            RenderBuddyError::CreateSurface(e) => Some(e),
+            RenderBuddyError::BufferMap(e) => Some(e),
+            _ => None,
         }
     }
 }
 
 impl From<CreateSurfaceError> for RenderBuddyError {
     fn from(e: CreateSurfaceError) -> RenderBuddyError {
-        RenderBuddyError {
-            message: format!("wgpu::CreateSurfaceError {:?}", &e.to_string()),
-        }
+        RenderBuddyError::CreateSurface(e)
     }
 }
 
 impl From<RequestDeviceError> for RenderBuddyError {
     fn from(e: RequestDeviceError) -> RenderBuddyError {
-        RenderBuddyError {
-            message: format!("wgpu::RequestDeviceError {:?}", &e.to_string()),
+        RenderBuddyError::RequestDevice(e)
+    }
+}
+
+impl From<SurfaceError> for RenderBuddyError {
+    fn from(e: SurfaceError) -> RenderBuddyError {
+        match e {
+            SurfaceError::Timeout => RenderBuddyError::SurfaceTimeout,
+            SurfaceError::Outdated => RenderBuddyError::SurfaceOutdated,
+            SurfaceError::Lost => RenderBuddyError::SurfaceLost,
+            SurfaceError::OutOfMemory => RenderBuddyError::OutOfMemory,
         }
     }
 }
+
+impl From<BufferAsyncError> for RenderBuddyError {
+    fn from(e: BufferAsyncError) -> RenderBuddyError {
+        RenderBuddyError::BufferMap(e)
+    }
+}
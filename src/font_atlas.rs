@@ -7,13 +7,51 @@ use wgpu::TextureFormat;
 
 use crate::{dynamic_texture_atlas_builder::DynamicTextureAtlasBuilder, texture::Image};
 
+/// Size of a new atlas page, pages double in size when they're full
+const INITIAL_PAGE_SIZE: f32 = 512.;
+/// Full pages stop growing at this size and glyphs go into a new page instead
+const MAX_PAGE_SIZE: f32 = 2048.;
+const GLYPH_PADDING: u32 = 1;
+
+/// Where a glyph is in the atlas
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AtlasGlyph {
+    pub page: usize,
+    pub index: usize,
+    pub metrics: Metrics,
+    /// Frame the glyph was last drawn in
+    pub last_used: u64,
+}
+
+/// The glyphs of a font at one size, spread over as many pages as they need, each page is its own texture
 pub(crate) struct FontAtlas {
-    pub dynamic_texture_atlas_builder: DynamicTextureAtlasBuilder,
-    pub glyph_atlas_info: HashMap<char, (usize, Metrics)>,
+    pub pages: Vec<DynamicTextureAtlasBuilder>,
+    pub glyph_atlas_info: HashMap<char, AtlasGlyph>,
+    /// Frame any glyph of the atlas was last drawn in
+    pub last_used: u64,
+    /// Frame each page was last built into a mesh in, meshes have uvs for the size of the page at the time
+    /// so pages drawn this frame can't grow until the next one
+    drawn_in: Vec<Option<u64>>,
+    /// The largest texture the device supports
+    max_texture_size: f32,
 }
 
 impl FontAtlas {
-    pub fn new(size: Vec2) -> FontAtlas {
+    pub fn new(max_texture_size: u32) -> FontAtlas {
+        let max_texture_size = max_texture_size as f32;
+
+        Self {
+            pages: vec![Self::new_page(Vec2::splat(
+                INITIAL_PAGE_SIZE.min(max_texture_size),
+            ))],
+            glyph_atlas_info: HashMap::default(),
+            last_used: 0,
+            drawn_in: vec![None],
+            max_texture_size,
+        }
+    }
+
+    fn new_page(size: Vec2) -> DynamicTextureAtlasBuilder {
         let temp_image_data = Image {
             data: vec![
                 0;
@@ -25,34 +63,243 @@ impl FontAtlas {
             sampler: crate::texture::TextureSamplerType::Nearest,
         };
 
-        let dynamic_texture_atlas_builder =
-            DynamicTextureAtlasBuilder::new(size, 1, temp_image_data);
+        DynamicTextureAtlasBuilder::new(size, GLYPH_PADDING, temp_image_data)
+    }
 
-        Self {
-            glyph_atlas_info: HashMap::default(),
-            dynamic_texture_atlas_builder,
+    pub fn get_glyph(&self, glyph_id: char) -> Option<AtlasGlyph> {
+        self.glyph_atlas_info.get(&glyph_id).copied()
+    }
+
+    /// Marks the glyph as drawn in this frame, returns false if it isn't in the atlas
+    pub fn use_glyph(&mut self, character: char, frame: u64) -> bool {
+        self.last_used = frame;
+        match self.glyph_atlas_info.get_mut(&character) {
+            Some(glyph) => {
+                glyph.last_used = frame;
+                true
+            }
+            None => false,
         }
     }
 
-    pub fn get_glyph_index(&self, glyph_id: char) -> Option<(usize, Metrics)> {
-        self.glyph_atlas_info.get(&glyph_id).copied()
+    /// Marks the pages holding the glyphs of the text as drawn this frame
+    pub fn draw_glyphs(&mut self, text: &str, frame: u64) {
+        for character in text.chars() {
+            if let Some(glyph) = self.glyph_atlas_info.get(&character) {
+                self.drawn_in[glyph.page] = Some(frame);
+            }
+        }
     }
 
-    pub fn has_glyph(&self, character: char) -> bool {
-        self.glyph_atlas_info.contains_key(&character)
+    /// Size of the CPU copy of the pages, their textures take up the same on the GPU
+    pub fn size_in_bytes(&self) -> usize {
+        self.pages
+            .iter()
+            .map(|page| page.temp_image_data.data.len())
+            .sum()
     }
 
+    /// Returns the page the glyph was added to, `None` if the glyph is bigger than the largest texture
+    /// When `evict_unused` is set, glyphs not drawn this frame make room before the atlas grows
     pub fn add_glyph(
         &mut self,
         glyph: char,
         image: &Image,
         glyph_metrics: Metrics,
-    ) -> Option<Image> {
-        if let Some((index, new_image)) = self.dynamic_texture_atlas_builder.add_texture(image) {
-            self.glyph_atlas_info.insert(glyph, (index, glyph_metrics));
-            return Some(new_image);
+        frame: u64,
+        evict_unused: bool,
+    ) -> Option<usize> {
+        let (page, index) = self.allocate(image, frame, evict_unused)?;
+        self.glyph_atlas_info.insert(
+            glyph,
+            AtlasGlyph {
+                page,
+                index,
+                metrics: glyph_metrics,
+                last_used: frame,
+            },
+        );
+
+        Some(page)
+    }
+
+    fn allocate(
+        &mut self,
+        image: &Image,
+        frame: u64,
+        evict_unused: bool,
+    ) -> Option<(usize, usize)> {
+        if let Some(allocation) = self.allocate_in_pages(image) {
+            return Some(allocation);
+        }
+        if evict_unused && self.evict_unused_glyphs(frame) {
+            if let Some(allocation) = self.allocate_in_pages(image) {
+                return Some(allocation);
+            }
+        }
+
+        // Earlier pages are already as big as they get, so only the last page grows
+        let max_page_size = MAX_PAGE_SIZE.min(self.max_texture_size);
+        let last_index = self.pages.len() - 1;
+        let last_page = &mut self.pages[last_index];
+        let drawn_this_frame = self.drawn_in[last_index] == Some(frame);
+        while !drawn_this_frame && last_page.texture_atlas.size.max_element() < max_page_size {
+            let size = (last_page.texture_atlas.size * 2.).min(Vec2::splat(max_page_size));
+            last_page.grow(size);
+            if let Some(index) = last_page.add_texture(image) {
+                return Some((last_index, index));
+            }
+        }
+
+        let glyph_size = image.dimensions.0.max(image.dimensions.1) + GLYPH_PADDING;
+        let page_size = INITIAL_PAGE_SIZE.max(glyph_size.next_power_of_two() as f32);
+        if page_size > self.max_texture_size {
+            return None;
         }
 
-        None
+        let mut page = Self::new_page(Vec2::splat(page_size));
+        let index = page.add_texture(image)?;
+        self.pages.push(page);
+        self.drawn_in.push(None);
+
+        Some((self.pages.len() - 1, index))
+    }
+
+    fn allocate_in_pages(&mut self, image: &Image) -> Option<(usize, usize)> {
+        self.pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(page_index, page)| Some((page_index, page.add_texture(image)?)))
+    }
+
+    /// Removes the glyphs that weren't drawn this frame, returns true if any were removed
+    fn evict_unused_glyphs(&mut self, frame: u64) -> bool {
+        let pages = &mut self.pages;
+        let glyph_count = self.glyph_atlas_info.len();
+        self.glyph_atlas_info.retain(|_, glyph| {
+            let used = glyph.last_used >= frame;
+            if !used {
+                pages[glyph.page].remove_texture(glyph.index);
+            }
+            used
+        });
+
+        self.glyph_atlas_info.len() < glyph_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyph_image(size: u32) -> Image {
+        Image {
+            data: vec![255; (size * size * 4) as usize],
+            dimensions: (size, size),
+            format: TextureFormat::Rgba8UnormSrgb,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn grows_then_adds_pages() {
+        let mut font_atlas = FontAtlas::new(4096);
+        let image = glyph_image(300);
+
+        let first_page = font_atlas.add_glyph('a', &image, Metrics::default(), 0, false);
+        assert_eq!(first_page, Some(0));
+        assert_eq!(font_atlas.pages[0].texture_atlas.size, Vec2::splat(512.));
+
+        font_atlas.add_glyph('b', &image, Metrics::default(), 0, false);
+        assert_eq!(font_atlas.pages[0].texture_atlas.size, Vec2::splat(1024.));
+        assert_eq!(font_atlas.get_glyph('a').unwrap().page, 0);
+
+        // Fill the first page until the glyphs spill into a second one
+        let mut characters = ('c'..='z').chain('A'..='Z');
+        while font_atlas.pages.len() == 1 {
+            let character = characters.next().unwrap();
+            font_atlas.add_glyph(character, &image, Metrics::default(), 0, false);
+        }
+        assert_eq!(font_atlas.pages[0].texture_atlas.size, Vec2::splat(2048.));
+        assert_eq!(font_atlas.pages[1].texture_atlas.size, Vec2::splat(512.));
+        assert_eq!(font_atlas.get_glyph('a').unwrap().page, 0);
+    }
+
+    #[test]
+    fn skips_glyphs_bigger_than_the_largest_texture() {
+        let mut font_atlas = FontAtlas::new(256);
+
+        assert_eq!(
+            font_atlas.add_glyph('a', &glyph_image(300), Metrics::default(), 0, false),
+            None
+        );
+        assert!(font_atlas.get_glyph('a').is_none());
+    }
+
+    #[test]
+    fn evicts_glyphs_unused_this_frame_instead_of_growing() {
+        let mut font_atlas = FontAtlas::new(4096);
+        let image = glyph_image(300);
+        font_atlas.add_glyph('a', &image, Metrics::default(), 0, true);
+
+        // Only one glyph fits the first page, 'a' isn't used anymore so it makes room
+        font_atlas.add_glyph('b', &image, Metrics::default(), 1, true);
+        assert_eq!(font_atlas.pages[0].texture_atlas.size, Vec2::splat(512.));
+        assert!(font_atlas.get_glyph('a').is_none());
+
+        // 'b' is used this frame so the atlas has to grow
+        font_atlas.add_glyph('c', &image, Metrics::default(), 1, true);
+        assert_eq!(font_atlas.pages[0].texture_atlas.size, Vec2::splat(1024.));
+        assert!(font_atlas.get_glyph('b').is_some());
+    }
+
+    #[test]
+    fn pages_drawn_this_frame_dont_grow() {
+        let mut font_atlas = FontAtlas::new(4096);
+        let image = glyph_image(300);
+        font_atlas.add_glyph('a', &image, Metrics::default(), 0, false);
+        font_atlas.draw_glyphs("a", 0);
+
+        // The first page is full and already drawn, so the glyph goes into a new page
+        assert_eq!(
+            font_atlas.add_glyph('b', &image, Metrics::default(), 0, false),
+            Some(1)
+        );
+        assert_eq!(font_atlas.pages[0].texture_atlas.size, Vec2::splat(512.));
+
+        // The new page isn't drawn yet so it can still grow
+        font_atlas.add_glyph('c', &image, Metrics::default(), 0, false);
+        assert_eq!(font_atlas.pages[1].texture_atlas.size, Vec2::splat(1024.));
+        assert_eq!(font_atlas.get_glyph('c').unwrap().page, 1);
+    }
+
+    #[test]
+    fn drawn_pages_grow_in_the_next_frame() {
+        let mut font_atlas = FontAtlas::new(4096);
+        let image = glyph_image(300);
+        font_atlas.add_glyph('a', &image, Metrics::default(), 0, false);
+        font_atlas.draw_glyphs("a", 0);
+
+        assert_eq!(
+            font_atlas.add_glyph('b', &image, Metrics::default(), 1, false),
+            Some(0)
+        );
+        assert_eq!(font_atlas.pages[0].texture_atlas.size, Vec2::splat(1024.));
+    }
+
+    #[test]
+    fn tracks_the_area_changed_by_new_glyphs() {
+        let mut font_atlas = FontAtlas::new(4096);
+        font_atlas.add_glyph('a', &glyph_image(10), Metrics::default(), 0, false);
+        font_atlas.add_glyph('b', &glyph_image(20), Metrics::default(), 0, false);
+
+        let page = &mut font_atlas.pages[0];
+        let dirty_rect = page.take_dirty_rect().unwrap();
+        for index in 0..2 {
+            let glyph_rect = page.texture_atlas.textures[index];
+            assert!(dirty_rect.min.cmple(glyph_rect.min).all());
+            assert!(dirty_rect.max.cmpge(glyph_rect.max).all());
+        }
+        assert!(page.take_dirty_rect().is_none());
     }
 }
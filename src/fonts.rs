@@ -1,16 +1,27 @@
 use crate::arena::{ArenaId, Handle};
+use crate::errors::RenderBuddyError;
 use crate::float_ord::FloatOrd;
 use crate::rect::Rect;
+use crate::sdf::{self, SDF_FONT_SIZE, SDF_SPREAD, SDF_SUPERSAMPLING};
 use crate::texture::{Image, Texture};
 use crate::RenderBuddy;
 use core::hash::{Hash, Hasher};
-use fontdue::{Font as ExternalFont, FontResult, Metrics};
+use fontdue::{Font as ExternalFont, Metrics, OutlineBounds};
 use glam::Vec2;
 use std::collections::HashMap;
 use wgpu::TextureFormat;
 
 pub type FontSizeKey = FloatOrd;
 
+/// The atlas glyphs of a font are rasterized into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GlyphAtlasKey {
+    /// Bitmaps rasterized at one font size
+    Bitmap(FontSizeKey),
+    /// Distance fields shared by text of every size
+    Sdf,
+}
+
 #[derive(Debug, Clone)]
 pub struct GlyphAtlasInfo {
     pub texture_rect: Rect,
@@ -22,13 +33,16 @@ pub struct GlyphAtlasInfo {
 #[derive(Debug, Clone)]
 pub struct PositionedGlyph {
     pub position: Vec2,
+    /// Size of the glyph's quad, bigger than the texture rect for SDF text drawn bigger than its field
+    pub size: Vec2,
     pub rect: Rect,
     pub atlas_info: GlyphAtlasInfo,
 }
 
 pub struct Font {
     pub font: ExternalFont,
-    pub texture_ids: HashMap<FloatOrd, Handle<Texture>>,
+    /// Textures of the glyph atlas pages for each atlas, in page order
+    pub texture_ids: HashMap<GlyphAtlasKey, Vec<Handle<Texture>>>,
 }
 
 impl Hash for Font {
@@ -38,8 +52,9 @@ impl Hash for Font {
 }
 
 impl Font {
-    pub fn try_from_bytes(font_data: &[u8]) -> FontResult<Self> {
-        let font = fontdue::Font::from_bytes(font_data, fontdue::FontSettings::default())?;
+    pub fn try_from_bytes(font_data: &[u8]) -> Result<Self, RenderBuddyError> {
+        let font = fontdue::Font::from_bytes(font_data, fontdue::FontSettings::default())
+            .map_err(RenderBuddyError::FontParse)?;
         Ok(Font {
             font,
             texture_ids: HashMap::default(),
@@ -61,18 +76,81 @@ impl Font {
 
         (metrics, glyph_image)
     }
+
+    /// Rasterizes the distance field of a character at [`SDF_FONT_SIZE`],
+    /// the metrics are those of the field, which extends [`SDF_SPREAD`] texels past the glyph
+    pub(crate) fn rasterize_sdf(&self, character: char) -> (Metrics, Image) {
+        let supersampling = SDF_SUPERSAMPLING as i32;
+        let spread = SDF_SPREAD as i32;
+        let (mask_metrics, mask) = self
+            .font
+            .rasterize(character, SDF_FONT_SIZE * SDF_SUPERSAMPLING as f32);
+
+        let xmin = mask_metrics.xmin.div_euclid(supersampling) - spread;
+        let ymin = mask_metrics.ymin.div_euclid(supersampling) - spread;
+        let xmax = (mask_metrics.xmin + mask_metrics.width as i32 + supersampling - 1)
+            .div_euclid(supersampling)
+            + spread;
+        let ymax = (mask_metrics.ymin + mask_metrics.height as i32 + supersampling - 1)
+            .div_euclid(supersampling)
+            + spread;
+        let field_size = ((xmax - xmin) as usize, (ymax - ymin) as usize);
+
+        // Rows go down from the top, so the mask starts below the top of the field
+        let mask_top = mask_metrics.ymin + mask_metrics.height as i32;
+        let field = sdf::distance_field(
+            &mask,
+            (mask_metrics.width, mask_metrics.height),
+            (
+                (mask_metrics.xmin - xmin * supersampling) as usize,
+                (ymax * supersampling - mask_top) as usize,
+            ),
+            field_size,
+        );
+
+        let scale = 1. / SDF_SUPERSAMPLING as f32;
+        let metrics = Metrics {
+            xmin,
+            ymin,
+            width: field_size.0,
+            height: field_size.1,
+            advance_width: mask_metrics.advance_width * scale,
+            advance_height: mask_metrics.advance_height * scale,
+            bounds: OutlineBounds {
+                xmin: mask_metrics.bounds.xmin * scale,
+                ymin: mask_metrics.bounds.ymin * scale,
+                width: mask_metrics.bounds.width * scale,
+                height: mask_metrics.bounds.height * scale,
+            },
+        };
+        let glyph_image = Image {
+            dimensions: (field_size.0 as _, field_size.1 as _),
+            data: field
+                .iter()
+                .flat_map(|distance| [255, 255, 255, *distance])
+                .collect::<Vec<u8>>(),
+            format: TextureFormat::Rgba8UnormSrgb,
+            sampler: crate::texture::TextureSamplerType::Linear,
+        };
+
+        (metrics, glyph_image)
+    }
 }
 
 impl RenderBuddy {
-    pub fn add_font(&mut self, font_data: &[u8]) -> FontResult<Handle<Font>> {
+    pub fn add_font(&mut self, font_data: &[u8]) -> Result<Handle<Font>, RenderBuddyError> {
         let font = Font::try_from_bytes(font_data)?;
 
         Ok(self.fonts.insert(font))
     }
 
-    pub fn add_font_as_default(&mut self, font_data: &[u8]) -> FontResult<Handle<Font>> {
+    pub fn add_font_as_default(
+        &mut self,
+        font_data: &[u8],
+    ) -> Result<Handle<Font>, RenderBuddyError> {
         let font = Font::try_from_bytes(font_data)?;
         let default_id = ArenaId::first();
+        self.clear_font_cache(Handle::new(default_id));
         *self
             .fonts
             .get_mut(Handle::new(default_id))
@@ -80,4 +158,62 @@ impl RenderBuddy {
 
         Ok(Handle::new(default_id))
     }
+
+    /// Frees the glyph atlases of every size of the font, glyphs are rasterized again the next time they're drawn
+    /// Their textures are kept until the end of the frame for the text that's already pushed
+    pub fn clear_font_cache(&mut self, font: Handle<Font>) {
+        let keys = self
+            .font_atlases
+            .keys()
+            .filter(|(_, font_id)| *font_id == font.id)
+            .copied()
+            .collect::<Vec<_>>();
+        for key in keys {
+            self.remove_font_atlas(key);
+        }
+    }
+
+    /// Bytes of glyph atlases kept between frames, once it's exceeded the atlases of the least recently drawn
+    /// font sizes are freed at the end of the frame and unused glyphs make room before atlases grow
+    pub fn set_glyph_cache_budget(&mut self, glyph_cache_budget: usize) {
+        self.glyph_cache_budget = glyph_cache_budget;
+    }
+
+    pub(crate) fn glyph_cache_size(&self) -> usize {
+        self.font_atlases
+            .values()
+            .map(|font_atlas| font_atlas.size_in_bytes())
+            .sum()
+    }
+
+    /// Frees the least recently drawn font atlases until the cache fits the budget,
+    /// atlases drawn this frame are kept even when they don't fit
+    pub(crate) fn evict_font_atlases(&mut self) {
+        let mut cache_size = self.glyph_cache_size();
+        while cache_size > self.glyph_cache_budget {
+            let Some((key, size)) = self
+                .font_atlases
+                .iter()
+                .filter(|(_, font_atlas)| font_atlas.last_used < self.frame)
+                .min_by_key(|(_, font_atlas)| font_atlas.last_used)
+                .map(|(key, font_atlas)| (*key, font_atlas.size_in_bytes()))
+            else {
+                break;
+            };
+
+            self.remove_font_atlas(key);
+            cache_size -= size;
+        }
+    }
+
+    /// The textures of the atlas are freed at the end of the frame
+    fn remove_font_atlas(&mut self, key: (GlyphAtlasKey, ArenaId)) {
+        self.font_atlases.remove(&key);
+        let texture_handles = self
+            .fonts
+            .get_mut(Handle::new(key.1))
+            .and_then(|font| font.texture_ids.remove(&key.0))
+            .unwrap_or_default();
+        self.removed_font_textures.extend(texture_handles);
+    }
 }
@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+
+use glam::{Vec2, Vec4};
+
+use crate::{shape::ShapeGeometry, transform::Transform};
+
+/// Gradients hold at most this many color stops, any further stops are ignored
+pub const MAX_COLOR_STOPS: usize = 8;
+
+/// Edges aren't split any further once they're shorter than this many pixels
+const MIN_EDGE_LENGTH: f32 = 4.;
+/// Edges aren't split where they cross a color stop this many pixels or less from their ends
+const MIN_STOP_DISTANCE: f32 = 0.5;
+/// Edges are split until the color in their middle is off by at most this much
+const COLOR_TOLERANCE: f32 = 1. / 255.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ColorStop {
+    /// Position of the stop along the gradient, from 0 to 1
+    pub offset: f32,
+    pub color: Vec4,
+}
+
+impl ColorStop {
+    pub fn new(offset: f32, color: Vec4) -> Self {
+        Self { offset, color }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GradientKind {
+    Linear { angle: f32 },
+    Radial { center: Vec2, radius: f32 },
+    Corners([Vec4; 4]),
+}
+
+/// A color that changes over a shape, used instead of the shape's color
+/// Positions are relative to the bounding box of the shape, `(0, 0)` is the bottom left corner and `(1, 1)` the top right
+///
+/// Colors are evaluated per vertex, shapes are split into smaller triangles wherever
+/// the gradient doesn't change linearly so it still looks smooth
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gradient {
+    kind: GradientKind,
+    stops: [ColorStop; MAX_COLOR_STOPS],
+    stop_count: usize,
+}
+
+impl Gradient {
+    /// A gradient along a line through the center of the shape, stretched so the corners are at offset 0 and 1
+    /// The angle is in radians counter clockwise, 0 goes from left to right
+    pub fn linear(angle: f32, stops: &[ColorStop]) -> Self {
+        Self::new(GradientKind::Linear { angle }, stops)
+    }
+
+    /// A gradient going out from `center`, reaching offset 1 at `radius`
+    /// The radius is relative to the size of the shape, so the gradient is an ellipse on shapes that aren't square
+    pub fn radial(center: Vec2, radius: f32, stops: &[ColorStop]) -> Self {
+        Self::new(GradientKind::Radial { center, radius }, stops)
+    }
+
+    /// Blends between a color in each corner of the shape's bounding box
+    pub fn corners(top_left: Vec4, top_right: Vec4, bottom_right: Vec4, bottom_left: Vec4) -> Self {
+        Self::new(
+            GradientKind::Corners([top_left, top_right, bottom_right, bottom_left]),
+            &[],
+        )
+    }
+
+    /// Goes from `top` to `bottom`, e.g. for skies
+    pub fn vertical(top: Vec4, bottom: Vec4) -> Self {
+        Self::linear(
+            -std::f32::consts::FRAC_PI_2,
+            &[ColorStop::new(0., top), ColorStop::new(1., bottom)],
+        )
+    }
+
+    /// Goes from `left` to `right`, e.g. for health bars
+    pub fn horizontal(left: Vec4, right: Vec4) -> Self {
+        Self::linear(0., &[ColorStop::new(0., left), ColorStop::new(1., right)])
+    }
+
+    fn new(kind: GradientKind, stops: &[ColorStop]) -> Self {
+        let stop_count = stops.len().min(MAX_COLOR_STOPS);
+        let mut sorted_stops = [ColorStop::default(); MAX_COLOR_STOPS];
+        sorted_stops[..stop_count].copy_from_slice(&stops[..stop_count]);
+        sorted_stops[..stop_count].sort_by(|a, b| a.offset.total_cmp(&b.offset));
+
+        Self {
+            kind,
+            stops: sorted_stops,
+            stop_count,
+        }
+    }
+
+    pub fn stops(&self) -> &[ColorStop] {
+        &self.stops[..self.stop_count]
+    }
+
+    /// The color at a point relative to the bounding box, `size` is the size of the bounding box
+    pub(crate) fn color_at(&self, point: Vec2, size: Vec2) -> Vec4 {
+        let offset = match self.kind {
+            GradientKind::Linear { angle } => {
+                let direction = Vec2::new(angle.cos(), angle.sin());
+                let length = (size * direction).abs().dot(Vec2::ONE);
+                if length <= f32::EPSILON {
+                    return self.sample(0.);
+                }
+                ((point - 0.5) * size).dot(direction) / length + 0.5
+            }
+            GradientKind::Radial { center, radius } => {
+                (point - center).length() / radius.max(f32::EPSILON)
+            }
+            GradientKind::Corners([top_left, top_right, bottom_right, bottom_left]) => {
+                let bottom = bottom_left.lerp(bottom_right, point.x);
+                let top = top_left.lerp(top_right, point.x);
+                return bottom.lerp(top, point.y);
+            }
+        };
+
+        self.sample(offset)
+    }
+
+    /// Where along the edge between two points relative to the bounding box it crosses a color stop,
+    /// from 0 at `from` to 1 at `to`, `None` if it doesn't cross any further than `margin` from its ends
+    fn stop_crossing(&self, from: Vec2, to: Vec2, size: Vec2, margin: f32) -> Option<f32> {
+        // Keeps points placed on a stop from counting as being on either side of it
+        const OFFSET_TOLERANCE: f32 = 1e-4;
+
+        let t = match self.kind {
+            GradientKind::Linear { angle } => {
+                let direction = Vec2::new(angle.cos(), angle.sin());
+                let length = (size * direction).abs().dot(Vec2::ONE);
+                if length <= f32::EPSILON {
+                    return None;
+                }
+                let offset = |point: Vec2| ((point - 0.5) * size).dot(direction) / length + 0.5;
+                let (from_offset, to_offset) = (offset(from), offset(to));
+                // The offset changes linearly along the edge
+                self.crossed_stop(from_offset, to_offset, OFFSET_TOLERANCE)
+                    .map(|stop| (stop - from_offset) / (to_offset - from_offset))
+            }
+            GradientKind::Radial { center, radius } => {
+                let radius = radius.max(f32::EPSILON);
+                let (from_offset, to_offset) = (
+                    (from - center).length() / radius,
+                    (to - center).length() / radius,
+                );
+                let edge = to - from;
+                let start = from - center;
+                let a = edge.length_squared();
+                if a <= f32::EPSILON {
+                    return None;
+                }
+                let b = 2. * start.dot(edge);
+
+                // The offset is lowest at the point closest to the center and only grows from there,
+                // so an edge crossing a stop twice is split there first
+                let closest = (-b / (2. * a)).clamp(0., 1.);
+                let closest_offset = (start + edge * closest).length() / radius;
+                if closest > margin
+                    && closest < 1. - margin
+                    && self
+                        .crossed_stop(closest_offset, from_offset.min(to_offset), OFFSET_TOLERANCE)
+                        .is_some()
+                {
+                    Some(closest)
+                } else {
+                    self.crossed_stop(from_offset, to_offset, OFFSET_TOLERANCE)
+                        .map(|stop| {
+                            // Solves |from + t * (to - from) - center| = stop * radius for the one solution on the edge
+                            let c = start.length_squared() - (stop * radius).powi(2);
+                            let root = (b * b - 4. * a * c).max(0.).sqrt();
+                            if from_offset < to_offset {
+                                (-b + root) / (2. * a)
+                            } else {
+                                (-b - root) / (2. * a)
+                            }
+                        })
+                }
+            }
+            GradientKind::Corners(_) => None,
+        }?;
+
+        (t > margin && t < 1. - margin).then_some(t)
+    }
+
+    /// The first stop strictly between two offsets
+    fn crossed_stop(&self, from: f32, to: f32, tolerance: f32) -> Option<f32> {
+        let (low, high) = (from.min(to), from.max(to));
+        self.stops()
+            .iter()
+            .map(|stop| stop.offset)
+            .find(|stop| *stop > low + tolerance && *stop < high - tolerance)
+    }
+
+    fn sample(&self, offset: f32) -> Vec4 {
+        let stops = self.stops();
+        let (Some(first), Some(last)) = (stops.first(), stops.last()) else {
+            return Vec4::ONE;
+        };
+        if offset <= first.offset {
+            return first.color;
+        }
+
+        for pair in stops.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            if offset <= to.offset {
+                let distance = to.offset - from.offset;
+                if distance <= f32::EPSILON {
+                    return to.color;
+                }
+                return from.color.lerp(to.color, (offset - from.offset) / distance);
+            }
+        }
+
+        last.color
+    }
+
+    /// Colors every point of the geometry, splitting its triangles until the colors in between are close enough
+    pub(crate) fn apply(&self, geometry: &mut ShapeGeometry, transform: &Transform) {
+        let scale = transform.scale.x.abs().max(transform.scale.y.abs());
+        let mut tessellator = GradientTessellator {
+            gradient: self,
+            min: geometry.min,
+            size: (geometry.max - geometry.min).max(Vec2::splat(f32::EPSILON)),
+            min_edge_length: MIN_EDGE_LENGTH / scale.max(f32::EPSILON),
+            min_stop_distance: MIN_STOP_DISTANCE / scale.max(f32::EPSILON),
+            points: std::mem::take(&mut geometry.points),
+            colors: Vec::new(),
+            midpoints: HashMap::new(),
+        };
+        tessellator.colors = tessellator
+            .points
+            .iter()
+            .map(|point| tessellator.color_at(*point))
+            .collect();
+
+        let mut triangles = geometry
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect::<Vec<[u32; 3]>>();
+        let mut indices = Vec::with_capacity(geometry.indices.len());
+        while let Some([a, b, c]) = triangles.pop() {
+            // Triangles without an area aren't drawn, splitting them only adds more of them
+            let (point_a, point_b, point_c) = (
+                tessellator.points[a as usize],
+                tessellator.points[b as usize],
+                tessellator.points[c as usize],
+            );
+            let longest = (point_b - point_a)
+                .length_squared()
+                .max((point_c - point_b).length_squared())
+                .max((point_a - point_c).length_squared());
+            if (point_b - point_a).perp_dot(point_c - point_a).abs() <= longest * 1e-4 {
+                indices.extend([a, b, c]);
+                continue;
+            }
+
+            let splits = (
+                tessellator.midpoint(a, b),
+                tessellator.midpoint(b, c),
+                tessellator.midpoint(c, a),
+            );
+            match splits {
+                (None, None, None) => indices.extend([a, b, c]),
+                (Some(ab), None, None) => triangles.extend([[a, ab, c], [ab, b, c]]),
+                (None, Some(bc), None) => triangles.extend([[a, b, bc], [a, bc, c]]),
+                (None, None, Some(ca)) => triangles.extend([[a, b, ca], [ca, b, c]]),
+                (Some(ab), Some(bc), None) => {
+                    triangles.extend([[ab, b, bc], [a, ab, bc], [a, bc, c]])
+                }
+                (None, Some(bc), Some(ca)) => {
+                    triangles.extend([[bc, c, ca], [a, b, bc], [a, bc, ca]])
+                }
+                (Some(ab), None, Some(ca)) => {
+                    triangles.extend([[a, ab, ca], [ab, b, c], [ab, c, ca]])
+                }
+                (Some(ab), Some(bc), Some(ca)) => {
+                    triangles.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]])
+                }
+            }
+        }
+
+        geometry.points = tessellator.points;
+        geometry.colors = tessellator.colors;
+        geometry.indices = indices;
+    }
+}
+
+struct GradientTessellator<'a> {
+    gradient: &'a Gradient,
+    min: Vec2,
+    size: Vec2,
+    min_edge_length: f32,
+    min_stop_distance: f32,
+    points: Vec<Vec2>,
+    colors: Vec<Vec4>,
+    /// Points added in the middle of edges, shared by the triangles on both sides
+    midpoints: HashMap<(u32, u32), u32>,
+}
+
+impl GradientTessellator<'_> {
+    fn color_at(&self, point: Vec2) -> Vec4 {
+        self.gradient
+            .color_at((point - self.min) / self.size, self.size)
+    }
+
+    /// Returns the point splitting the edge if it needs to be split
+    /// Edges are split where they cross a color stop so every stop gets its own vertices,
+    /// and in the middle while the color there is too far from the colors at their ends
+    /// Only depends on the edge, so triangles sharing it split it the same way and no gaps open up
+    fn midpoint(&mut self, a: u32, b: u32) -> Option<u32> {
+        let key = (a.min(b), a.max(b));
+        if let Some(index) = self.midpoints.get(&key) {
+            return Some(*index);
+        }
+
+        let (from, to) = (self.points[key.0 as usize], self.points[key.1 as usize]);
+        let crossing = self.gradient.stop_crossing(
+            (from - self.min) / self.size,
+            (to - self.min) / self.size,
+            self.size,
+            self.min_stop_distance / from.distance(to).max(f32::EPSILON),
+        );
+        let point = match crossing {
+            Some(t) => from.lerp(to, t),
+            None => {
+                if from.distance(to) < self.min_edge_length {
+                    return None;
+                }
+                let point = (from + to) / 2.;
+                let interpolated = (self.colors[a as usize] + self.colors[b as usize]) / 2.;
+                if (self.color_at(point) - interpolated).abs().max_element() <= COLOR_TOLERANCE {
+                    return None;
+                }
+                point
+            }
+        };
+        let color = self.color_at(point);
+
+        let index = self.points.len() as u32;
+        self.points.push(point);
+        self.colors.push(color);
+        self.midpoints.insert(key, index);
+
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_between_stops() {
+        let gradient = Gradient::linear(
+            0.,
+            &[
+                ColorStop::new(1., Vec4::ONE),
+                ColorStop::new(0., Vec4::ZERO),
+                ColorStop::new(0.5, Vec4::splat(0.25)),
+            ],
+        );
+
+        assert_eq!(gradient.sample(-1.), Vec4::ZERO);
+        assert_eq!(gradient.sample(0.25), Vec4::splat(0.125));
+        assert_eq!(gradient.sample(0.75), Vec4::splat(0.625));
+        assert_eq!(gradient.sample(2.), Vec4::ONE);
+    }
+
+    #[test]
+    fn linear_gradient_reaches_corners() {
+        let gradient = Gradient::linear(
+            std::f32::consts::FRAC_PI_4,
+            &[
+                ColorStop::new(0., Vec4::ZERO),
+                ColorStop::new(1., Vec4::ONE),
+            ],
+        );
+        let size = Vec2::new(200., 50.);
+
+        assert!(gradient
+            .color_at(Vec2::ZERO, size)
+            .abs_diff_eq(Vec4::ZERO, 1e-5));
+        assert!(gradient
+            .color_at(Vec2::ONE, size)
+            .abs_diff_eq(Vec4::ONE, 1e-5));
+    }
+
+    #[test]
+    fn every_stop_gets_vertices() {
+        let (black, white) = (Vec4::new(0., 0., 0., 1.), Vec4::ONE);
+        let gradient = Gradient::linear(
+            0.,
+            &[
+                ColorStop::new(0., black),
+                ColorStop::new(0.25, white),
+                ColorStop::new(0.5, black),
+                ColorStop::new(0.75, white),
+                ColorStop::new(1., black),
+            ],
+        );
+        let mut geometry = ShapeGeometry::from_points(
+            vec![
+                Vec2::ZERO,
+                Vec2::new(100., 0.),
+                Vec2::new(100., 50.),
+                Vec2::new(0., 50.),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        );
+
+        gradient.apply(&mut geometry, &Transform::default());
+
+        assert!(geometry
+            .indices
+            .iter()
+            .all(|index| (*index as usize) < geometry.points.len()));
+        assert_eq!(geometry.colors.len(), geometry.points.len());
+        for stop in gradient.stops() {
+            // A vertex on the stop at the top and bottom edge, with the color of the stop
+            for y in [0., 50.] {
+                let stop_point = Vec2::new(stop.offset * 100., y);
+                let vertex = geometry
+                    .points
+                    .iter()
+                    .position(|point| point.abs_diff_eq(stop_point, 1e-3))
+                    .unwrap_or_else(|| panic!("No vertex at {stop_point}"));
+                assert!(geometry.colors[vertex].abs_diff_eq(stop.color, 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn radial_edges_are_split_on_stops() {
+        let gradient = Gradient::radial(
+            Vec2::splat(0.5),
+            0.5,
+            &[
+                ColorStop::new(0., Vec4::ONE),
+                ColorStop::new(0.5, Vec4::ZERO),
+                ColorStop::new(1., Vec4::ONE),
+            ],
+        );
+        let mut geometry = ShapeGeometry::from_points(
+            vec![Vec2::ZERO, Vec2::new(100., 0.), Vec2::splat(100.)],
+            vec![0, 1, 2],
+        );
+
+        gradient.apply(&mut geometry, &Transform::default());
+
+        // The diagonal goes through the center, crossing the middle stop on both sides of it
+        let distance = 25. * std::f32::consts::FRAC_1_SQRT_2;
+        for point in [Vec2::splat(50. - distance), Vec2::splat(50. + distance)] {
+            let vertex = geometry
+                .points
+                .iter()
+                .position(|vertex| vertex.abs_diff_eq(point, 1e-3))
+                .unwrap();
+            assert!(geometry.colors[vertex].abs_diff_eq(Vec4::ZERO, 1e-5));
+        }
+    }
+
+    #[test]
+    fn radial_stops_touching_edges_finish_splitting() {
+        // The outer circle touches every side of the square, where edges barely cross it
+        let gradient = Gradient::radial(
+            Vec2::splat(0.5),
+            0.5,
+            &[
+                ColorStop::new(0., Vec4::ONE),
+                ColorStop::new(0.3, Vec4::ZERO),
+                ColorStop::new(0.6, Vec4::ONE),
+                ColorStop::new(1., Vec4::ZERO),
+            ],
+        );
+        let mut geometry = ShapeGeometry::from_points(
+            vec![
+                Vec2::ZERO,
+                Vec2::new(100., 0.),
+                Vec2::splat(100.),
+                Vec2::new(0., 100.),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        );
+
+        gradient.apply(&mut geometry, &Transform::default());
+
+        assert!(geometry.points.len() < 10_000);
+    }
+}
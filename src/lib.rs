@@ -1,40 +1,54 @@
 use std::{collections::HashMap, sync::Arc};
 
 use arena::{Arena, ArenaId, Handle};
-use batching::PreparedMeshBatch;
+use batching::{PreparedMeshBatch, TextureBindGroupKey, TextureBindGroupLayouts};
 use bind_groups::BindGroupLayoutBuilder;
-use camera::Camera;
+use camera::{Camera, CameraUniform};
+use descriptor::{select_present_mode, RenderBuddyDescriptor};
+use dynamic_buffer::DynamicBuffer;
 use errors::RenderBuddyError;
 use font_atlas::FontAtlas;
-use fonts::{Font, FontSizeKey};
+use fonts::{Font, GlyphAtlasKey};
 use glam::{Quat, Vec3, Vec4};
-use material::DefaultMat;
+use material::{DefaultMat, MaskMat, RoundedRectMat, SdfTextMat};
 use mesh::{BatchMeshCreator, Mesh, MeshCreator};
-use pipeline::Pipeline;
+use pipeline::{Pipeline, PipelineKey};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use rect::Rect;
 use render_context::RenderContext;
-use texture::{Texture, TextureSamplerType};
+use stencil::{has_stencil, StencilMode};
+use texture::{Image, Texture, TextureSamplerType};
 use transform::Transform;
 use wgpu::{
-    BindGroup, BindGroupLayout, BindingType, RenderPass, Sampler, ShaderStages,
-    SurfaceConfiguration,
+    util::align_to, BindGroup, BindGroupLayout, BindingType, Buffer, BufferAddress, BufferUsages,
+    Device, RenderPass, Sampler, ShaderStages, SurfaceConfiguration, TextureFormat,
 };
 
 pub mod arena;
 pub mod batching;
 pub mod bind_groups;
 pub mod camera;
+pub mod descriptor;
+mod dynamic_buffer;
 pub mod dynamic_texture_atlas_builder;
 pub mod errors;
 mod float_ord;
 mod font_atlas;
 pub mod fonts;
+pub mod gradient;
+pub mod line;
 pub mod material;
 pub mod mesh;
+pub mod nine_slice;
+pub mod path;
 pub mod pipeline;
+pub mod polygon;
 pub mod rect;
 mod render_context;
+mod sdf;
+pub mod shape;
 pub mod sprite;
+pub mod stencil;
 pub mod text;
 pub mod texture;
 pub mod texture_atlas;
@@ -43,25 +57,65 @@ pub mod transform;
 pub use glam;
 pub use wgpu;
 
+const INITIAL_VERTEX_BUFFER_SIZE: BufferAddress = 1 << 20;
+const INITIAL_INDEX_BUFFER_SIZE: BufferAddress = 1 << 18;
+const CAMERA_UNIFORM_SIZE: BufferAddress = std::mem::size_of::<CameraUniform>() as BufferAddress;
+
 pub struct MaterialMap {
     default: Handle<Pipeline>,
+    /// Used instead of the default material for masks, discards transparent pixels
+    mask: Handle<Pipeline>,
+    /// Used for rects with rounded corners or a border
+    rounded_rect: Handle<Pipeline>,
+    /// Used for text drawn with distance fields
+    sdf_text: Handle<Pipeline>,
 }
 
 pub struct RenderBuddy {
     pub(crate) fonts: Arena<Font>,
-    pub(crate) font_atlases: HashMap<(FontSizeKey, ArenaId), FontAtlas>,
+    pub(crate) font_atlases: HashMap<(GlyphAtlasKey, ArenaId), FontAtlas>,
+    /// Bytes of font atlases to keep, see [`RenderBuddy::set_glyph_cache_budget`]
+    pub(crate) glyph_cache_budget: usize,
+    /// Number of frames ended so far, used to find the font atlases that haven't been drawn for the longest
+    pub(crate) frame: u64,
+    /// Textures of removed font atlases, text pushed earlier in the frame may still use them until it ends
+    pub(crate) removed_font_textures: Vec<Handle<Texture>>,
     pub textures: Arena<Texture>,
     pub device: wgpu::Device,
     pub(crate) meshes: Vec<Mesh>,
+    /// Clip rects pushed with [`RenderBuddy::push_clip`], each already intersected with the one below
+    pub(crate) clip_stack: Vec<Rect>,
     pub(crate) default_texture_samplers: HashMap<TextureSamplerType, Handle<Sampler>>,
     pub samplers: Arena<Sampler>,
     camera_bind_group_layout: BindGroupLayout,
+    /// Holds one camera uniform per render of the frame, bound with a dynamic offset
+    camera_buffer: DynamicBuffer,
+    camera_bind_group: BindGroup,
+    /// Vertices and indices of every batch in the frame
+    pub(crate) vertex_buffer: DynamicBuffer,
+    pub(crate) index_buffer: DynamicBuffer,
+    pub(crate) texture_bind_group_layouts: TextureBindGroupLayouts,
+    pub(crate) texture_bind_groups: HashMap<TextureBindGroupKey, Arc<BindGroup>>,
     queue: wgpu::Queue,
-    surface: wgpu::Surface,
+    surface: Option<wgpu::Surface>,
     surface_config: SurfaceConfiguration,
+    backend: wgpu::Backend,
+    supported_present_modes: Vec<wgpu::PresentMode>,
+    /// The offscreen color target used instead of a surface in headless mode
+    pub(crate) headless_texture_handle: Option<Handle<Texture>>,
+    /// MSAA sample count used when rendering to the surface
+    pub(crate) sample_count: u32,
+    /// The color target rendered into and resolved to the surface when MSAA is enabled
+    pub(crate) multisampled_texture_handle: Option<Handle<Texture>>,
     materials: Arena<Pipeline>,
     pub(crate) material_map: MaterialMap,
     pub(crate) depth_texture_handle: Handle<Texture>,
+    /// Format of the surface and render target depth textures, includes stencil if enabled
+    pub(crate) depth_format: TextureFormat,
+    /// Stencil mode and reference set with [`RenderBuddy::set_stencil`]
+    pub(crate) stencil: (StencilMode, u32),
+    /// Depth textures for each render target, keyed by the target texture
+    pub(crate) render_target_depth_textures: HashMap<ArenaId, Handle<Texture>>,
 }
 
 impl RenderBuddy {
@@ -69,25 +123,37 @@ impl RenderBuddy {
     /// Creates a WGPU Surface, Instance, Device and Queue
     /// Requires async to request instance adapter
     pub async fn new<W>(window: &W, viewport_size: (u32, u32)) -> Result<Self, RenderBuddyError>
+    where
+        W: HasRawWindowHandle + HasRawDisplayHandle,
+    {
+        Self::new_with_descriptor(window, viewport_size, &RenderBuddyDescriptor::default()).await
+    }
+
+    /// Creates a [`RenderBuddy`] Instance configured by a [`RenderBuddyDescriptor`]
+    pub async fn new_with_descriptor<W>(
+        window: &W,
+        viewport_size: (u32, u32),
+        descriptor: &RenderBuddyDescriptor,
+    ) -> Result<Self, RenderBuddyError>
     where
         W: HasRawWindowHandle + HasRawDisplayHandle,
     {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
+            backends: descriptor.backends,
             dx12_shader_compiler: Default::default(),
         });
 
         let surface = unsafe { instance.create_surface(&window)? };
         let adapter = match instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
+                power_preference: descriptor.power_preference,
                 compatible_surface: Some(&surface),
                 force_fallback_adapter: false,
             })
             .await
         {
             Some(it) => it,
-            None => return Err(RenderBuddyError::new("Unable to request adapter from wgpu")),
+            None => return Err(RenderBuddyError::AdapterNotFound),
         };
 
         let surface_caps = surface.get_capabilities(&adapter);
@@ -96,90 +162,166 @@ impl RenderBuddy {
             .formats
             .iter()
             .copied()
-            .find(|f| f.describe().srgb)
+            .find(|f| f.describe().srgb == descriptor.srgb)
             .unwrap_or(surface_caps.formats[0]);
+        let present_mode = descriptor
+            .present_mode
+            .map(|present_mode| select_present_mode(present_mode, &surface_caps.present_modes))
+            .unwrap_or(surface_caps.present_modes[0]);
+        let alpha_mode = descriptor
+            .alpha_mode
+            .filter(|alpha_mode| surface_caps.alpha_modes.contains(alpha_mode))
+            .unwrap_or(surface_caps.alpha_modes[0]);
         let surface_config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            usage: surface_usage(adapter.get_info().backend, descriptor.frame_capture),
             format: surface_format,
             width: viewport_size.0,
             height: viewport_size.1,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
+            present_mode,
+            alpha_mode,
+            view_formats: Vec::default(),
+        };
+
+        Self::from_adapter(
+            adapter,
+            Some(surface),
+            surface_config,
+            surface_caps.present_modes,
+            descriptor,
+        )
+        .await
+    }
+
+    /// Creates a [`RenderBuddy`] Instance without a window
+    /// Renders into an offscreen texture of the given size and format instead of a surface,
+    /// falls back to a software adapter when no hardware adapter is available
+    pub async fn new_headless(
+        viewport_size: (u32, u32),
+        format: TextureFormat,
+    ) -> Result<Self, RenderBuddyError> {
+        Self::new_headless_with_descriptor(viewport_size, format, &RenderBuddyDescriptor::default())
+            .await
+    }
+
+    /// Creates a headless [`RenderBuddy`] Instance configured by a [`RenderBuddyDescriptor`]
+    /// The surface options of the descriptor are ignored
+    pub async fn new_headless_with_descriptor(
+        viewport_size: (u32, u32),
+        format: TextureFormat,
+        descriptor: &RenderBuddyDescriptor,
+    ) -> Result<Self, RenderBuddyError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: descriptor.backends,
+            dx12_shader_compiler: Default::default(),
+        });
+
+        let mut adapter_options = wgpu::RequestAdapterOptions {
+            power_preference: descriptor.power_preference,
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        };
+
+        let adapter = match instance.request_adapter(&adapter_options).await {
+            Some(it) => it,
+            None => {
+                adapter_options.force_fallback_adapter = true;
+                match instance.request_adapter(&adapter_options).await {
+                    Some(it) => it,
+                    None => return Err(RenderBuddyError::AdapterNotFound),
+                }
+            }
+        };
+
+        // Only the size and format are used when there is no surface to configure
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: viewport_size.0,
+            height: viewport_size.1,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
             view_formats: Vec::default(),
         };
 
+        Self::from_adapter(adapter, None, surface_config, Vec::default(), descriptor).await
+    }
+
+    async fn from_adapter(
+        adapter: wgpu::Adapter,
+        surface: Option<wgpu::Surface>,
+        surface_config: SurfaceConfiguration,
+        supported_present_modes: Vec<wgpu::PresentMode>,
+        descriptor: &RenderBuddyDescriptor,
+    ) -> Result<Self, RenderBuddyError> {
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    // Webgl 2 for web until WGPU is fully supported
-                    limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
+                    features: descriptor.features,
+                    limits: descriptor.limits(),
                     label: None,
                 },
                 None,
             )
             .await?;
+        let backend = adapter.get_info().backend;
 
-        surface.configure(&device, &surface_config);
-
-        let default_sampler_nearest = {
-            device.create_sampler(&wgpu::SamplerDescriptor {
-                mag_filter: wgpu::FilterMode::Nearest,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                ..Default::default()
-            })
-        };
+        for format in [surface_config.format, descriptor.depth_format()] {
+            if !sample_count_supported(&adapter, &device, format, descriptor.sample_count) {
+                return Err(RenderBuddyError::UnsupportedSampleCount {
+                    sample_count: descriptor.sample_count,
+                    format,
+                });
+            }
+        }
 
-        let default_sampler_linear = {
-            device.create_sampler(&wgpu::SamplerDescriptor {
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Linear,
-                mipmap_filter: wgpu::FilterMode::Linear,
-                ..Default::default()
-            })
-        };
+        // Textures can't be empty, a window created minimized is configured once it's resized
+        let surface_size = (surface_config.width.max(1), surface_config.height.max(1));
+        if let Some(surface) = &surface {
+            if surface_config.width > 0 && surface_config.height > 0 {
+                surface.configure(&device, &surface_config);
+            }
+        }
 
         let mut samplers = Arena::new();
 
-        let default_sampler_linear = samplers.insert(default_sampler_linear);
-        let default_sampler_nearest = samplers.insert(default_sampler_nearest);
+        let default_texture_samplers = TextureSamplerType::ALL
+            .into_iter()
+            .map(|sampler_type| {
+                (
+                    sampler_type,
+                    samplers.insert(sampler_type.create_sampler(&device)),
+                )
+            })
+            .collect::<HashMap<TextureSamplerType, Handle<Sampler>>>();
+        let default_sampler_linear = default_texture_samplers[&TextureSamplerType::Linear];
+        let depth_texture_sampler_handle = default_texture_samplers[&TextureSamplerType::Depth];
 
         let camera_bind_group_layout = BindGroupLayoutBuilder::new()
             .append(
                 ShaderStages::VERTEX,
                 BindingType::Buffer {
                     ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(CAMERA_UNIFORM_SIZE),
                 },
                 None,
             )
             .build(&device, Some("camera_bind_group_layout"));
-
-        let depth_texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            compare: None, // 5.
-            lod_min_clamp: 0.0,
-            lod_max_clamp: 100.0,
-            ..Default::default()
-        });
-
-        let depth_texture_sampler_handle = samplers.insert(depth_texture_sampler);
+        let camera_buffer = DynamicBuffer::new(
+            &device,
+            "Camera Buffer",
+            BufferUsages::UNIFORM,
+            camera_uniform_stride(&device) * 4,
+        );
+        let camera_bind_group =
+            create_camera_bind_group(&device, &camera_bind_group_layout, camera_buffer.buffer());
 
         let depth_texture = Texture::create_depth_texture(
             &device,
-            &surface_config,
-            depth_texture_sampler_handle.clone(),
+            surface_size,
+            descriptor.depth_format(),
+            descriptor.sample_count,
+            depth_texture_sampler_handle,
         );
 
         let blank_texture = Texture::create_blank_texture(&device, &queue, default_sampler_linear);
@@ -189,40 +331,92 @@ impl RenderBuddy {
         textures.insert(blank_texture);
         let depth_texture_handle = textures.insert(depth_texture);
 
+        let headless_texture_handle = if surface.is_none() {
+            Some(textures.insert(Texture::create_render_target(
+                &device,
+                surface_size,
+                surface_config.format,
+                default_sampler_linear,
+            )))
+        } else {
+            None
+        };
+
+        let multisampled_texture_handle = if descriptor.sample_count > 1 {
+            Some(textures.insert(Texture::create_multisampled_texture(
+                &device,
+                surface_size,
+                surface_config.format,
+                descriptor.sample_count,
+                default_sampler_linear,
+            )))
+        } else {
+            None
+        };
+
         let mut render_buddy = Self {
             camera_bind_group_layout,
+            camera_buffer,
+            camera_bind_group,
+            vertex_buffer: DynamicBuffer::new(
+                &device,
+                "Vertex Buffer",
+                BufferUsages::VERTEX,
+                INITIAL_VERTEX_BUFFER_SIZE,
+            ),
+            index_buffer: DynamicBuffer::new(
+                &device,
+                "Index Buffer",
+                BufferUsages::INDEX,
+                INITIAL_INDEX_BUFFER_SIZE,
+            ),
+            texture_bind_group_layouts: TextureBindGroupLayouts::new(&device),
+            texture_bind_groups: HashMap::default(),
             font_atlases: HashMap::default(),
+            glyph_cache_budget: descriptor.glyph_cache_budget,
+            frame: 0,
+            removed_font_textures: Vec::new(),
             fonts: Arena::new(),
             device,
             meshes: Vec::default(),
+            clip_stack: Vec::default(),
             textures,
             queue,
             surface,
             surface_config,
+            backend,
+            supported_present_modes,
+            headless_texture_handle,
+            sample_count: descriptor.sample_count,
+            multisampled_texture_handle,
             samplers,
-            default_texture_samplers: HashMap::from([
-                (TextureSamplerType::Linear, default_sampler_linear),
-                (TextureSamplerType::Nearest, default_sampler_nearest),
-                (TextureSamplerType::Depth, depth_texture_sampler_handle),
-            ]),
+            default_texture_samplers,
             materials: Arena::new(),
             material_map: MaterialMap {
                 default: Handle::default(),
+                mask: Handle::default(),
+                rounded_rect: Handle::default(),
+                sdf_text: Handle::default(),
             },
             depth_texture_handle,
+            depth_format: descriptor.depth_format(),
+            stencil: (StencilMode::Disabled, 0),
+            render_target_depth_textures: HashMap::default(),
         };
 
-        let default_mat = DefaultMat {};
-        let render_pipeline = render_buddy.create_pipeline_from_material(&default_mat);
-        let material_handle = render_buddy.materials.insert(Pipeline {
-            render_pipeline,
-            material: Box::from(default_mat),
-        });
-        render_buddy.material_map.default = material_handle;
+        render_buddy.material_map.default =
+            render_buddy.insert_material(Box::<DefaultMat>::default())?;
+        render_buddy.material_map.mask = render_buddy.insert_material(Box::from(MaskMat {}))?;
+        render_buddy.material_map.rounded_rect =
+            render_buddy.insert_material(Box::from(RoundedRectMat {}))?;
+        render_buddy.material_map.sdf_text =
+            render_buddy.insert_material(Box::from(SdfTextMat {}))?;
 
-        render_buddy.fonts.insert(
-            Font::try_from_bytes(include_bytes!("./default_font/Roboto-Regular.ttf")).unwrap(),
-        );
+        render_buddy
+            .fonts
+            .insert(Font::try_from_bytes(include_bytes!(
+                "./default_font/Roboto-Regular.ttf"
+            ))?);
 
         Ok(render_buddy)
     }
@@ -231,6 +425,23 @@ impl RenderBuddy {
         (self.surface_config.width, self.surface_config.height)
     }
 
+    pub fn get_present_mode(&self) -> wgpu::PresentMode {
+        self.surface_config.present_mode
+    }
+
+    /// Reconfigures the surface with a new present mode, useful for toggling vsync at runtime
+    /// Falls back to [`wgpu::PresentMode::Fifo`] if the surface doesn't support it
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        self.surface_config.present_mode =
+            select_present_mode(present_mode, &self.supported_present_modes);
+
+        if let Some(surface) = &self.surface {
+            if self.surface_config.width > 0 && self.surface_config.height > 0 {
+                surface.configure(&self.device, &self.surface_config);
+            }
+        }
+    }
+
     /// Pushes a mesh to the render queue, must implement MeshBuilder
     pub fn push(&mut self, mesh: impl MeshCreator, position: Vec3) {
         self.push_transform(mesh, Transform::from_position(position));
@@ -260,8 +471,8 @@ impl RenderBuddy {
     }
     /// Pushes a mesh to the render queue, with a full transform
     pub fn push_transform(&mut self, mesh: impl MeshCreator, transform: Transform) {
-        let mesh = mesh.build(transform, &self);
-        self.meshes.push(mesh);
+        let mesh = mesh.build(transform, self);
+        self.push_mesh(mesh);
     }
 
     /// Pushes a group of meshes, useful for pushing a batch of meshes, mainly used for text rendering
@@ -272,28 +483,70 @@ impl RenderBuddy {
 
     /// Pushes a group of meshes, with a transform
     pub fn append_transform(&mut self, meshes: impl BatchMeshCreator, transform: Transform) {
-        let mut meshes = meshes.build(transform, self);
-        self.meshes.append(&mut meshes);
+        let meshes = meshes.build(transform, self);
+        self.append_meshes(meshes);
     }
 
-    pub fn push_mesh(&mut self, mesh: Mesh) {
+    pub fn push_mesh(&mut self, mut mesh: Mesh) {
+        if mesh.clip.is_none() {
+            mesh.clip = self.clip_stack.last().copied();
+        }
+        if mesh.stencil_mode == StencilMode::Disabled {
+            (mesh.stencil_mode, mesh.stencil_reference) = self.stencil;
+        }
+        if mesh.stencil_mode == StencilMode::Mask
+            && mesh.material_handle == self.material_map.default
+        {
+            mesh.material_handle = self.material_map.mask;
+        }
         self.meshes.push(mesh);
     }
 
-    pub fn append_meshes(&mut self, mut meshes: Vec<Mesh>) {
-        self.meshes.append(&mut meshes);
+    pub fn append_meshes(&mut self, meshes: Vec<Mesh>) {
+        for mesh in meshes {
+            self.push_mesh(mesh);
+        }
     }
 
     /// Begin the render process by prepping the [`RenderContext`]
-    pub fn begin(&self) -> RenderContext {
-        let output = self
-            .surface
-            .get_current_texture()
-            .expect("Missing current texture in surface");
+    /// A lost or outdated surface is reconfigured and acquired again,
+    /// errors where [`RenderBuddyError::should_skip_frame`] is true mean the frame should just be skipped
+    pub fn begin(&mut self) -> Result<RenderContext, RenderBuddyError> {
+        if self.surface_config.width == 0 || self.surface_config.height == 0 {
+            return Err(RenderBuddyError::ZeroSizedViewport);
+        }
 
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let (output, view) = match &self.surface {
+            Some(surface) => {
+                let output = match surface.get_current_texture() {
+                    Ok(output) => output,
+                    Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                        self.configure_surface();
+                        self.surface.as_ref().unwrap().get_current_texture()?
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+
+                (Some(output), view)
+            }
+            None => {
+                let texture = self
+                    .textures
+                    .get(
+                        self.headless_texture_handle
+                            .expect("Missing headless texture"),
+                    )
+                    .expect("Missing headless texture");
+                let view = texture
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+
+                (None, view)
+            }
+        };
 
         let command_encoder = self
             .device
@@ -301,11 +554,11 @@ impl RenderBuddy {
                 label: Some("Render Encoder"),
             });
 
-        RenderContext {
+        Ok(RenderContext {
             output,
             view,
             command_encoder,
-        }
+        })
     }
 
     /// Render the queue
@@ -317,11 +570,109 @@ impl RenderBuddy {
         camera: &Camera,
         use_depth_stencil_attachment: bool,
     ) {
-        let mesh_prepared_batch = self.prepare_mesh_batch();
-        let camera_bind_group = camera.create_bind_group(
-            &self.device,
-            (self.surface_config.width, self.surface_config.height),
-            &self.camera_bind_group_layout,
+        self.render_pass(
+            render_context,
+            None,
+            clear_color,
+            camera,
+            use_depth_stencil_attachment,
+        );
+    }
+
+    /// Render the queue into a render target created with [`RenderBuddy::create_render_target`]
+    /// instead of the surface, the target can then be drawn like any other texture in a later render
+    pub fn render_to_target(
+        &mut self,
+        render_context: &mut RenderContext,
+        target: Handle<Texture>,
+        clear_color: Option<Vec4>,
+        camera: &Camera,
+        use_depth_stencil_attachment: bool,
+    ) -> Result<(), RenderBuddyError> {
+        if !self.textures.contains(target)
+            || !self.render_target_depth_textures.contains_key(&target.id)
+        {
+            return Err(RenderBuddyError::InvalidHandle("render target"));
+        }
+
+        self.render_pass(
+            render_context,
+            Some(target),
+            clear_color,
+            camera,
+            use_depth_stencil_attachment,
+        );
+
+        Ok(())
+    }
+
+    fn render_pass(
+        &mut self,
+        render_context: &mut RenderContext,
+        target: Option<Handle<Texture>>,
+        clear_color: Option<Vec4>,
+        camera: &Camera,
+        use_depth_stencil_attachment: bool,
+    ) {
+        let stencil_enabled = has_stencil(self.depth_format);
+        // Meshes using the stencil need the depth stencil attachment even if depth isn't used
+        let use_depth_stencil_attachment = use_depth_stencil_attachment
+            || (stencil_enabled
+                && self
+                    .meshes
+                    .iter()
+                    .any(|mesh| mesh.stencil_mode != StencilMode::Disabled));
+        let depth_format = use_depth_stencil_attachment.then_some(self.depth_format);
+
+        let (target_size, pipeline_key, depth_texture_handle) = match target {
+            Some(target) => {
+                let texture = self
+                    .textures
+                    .get(target)
+                    .expect("Missing render target texture");
+                let depth_texture_handle = *self
+                    .render_target_depth_textures
+                    .get(&target.id)
+                    .expect("Texture is not a render target");
+
+                (
+                    (texture.dimensions.x as u32, texture.dimensions.y as u32),
+                    PipelineKey {
+                        format: texture.texture.format(),
+                        sample_count: 1,
+                        depth_format,
+                        stencil_mode: StencilMode::Disabled,
+                    },
+                    depth_texture_handle,
+                )
+            }
+            None => (
+                (self.surface_config.width, self.surface_config.height),
+                PipelineKey {
+                    depth_format,
+                    ..self.surface_pipeline_key()
+                },
+                self.depth_texture_handle,
+            ),
+        };
+
+        let mesh_prepared_batch = self.prepare_mesh_batch(pipeline_key);
+
+        let camera_uniform_stride = camera_uniform_stride(&self.device);
+        if self
+            .camera_buffer
+            .reserve(&self.device, camera_uniform_stride)
+        {
+            self.camera_bind_group = create_camera_bind_group(
+                &self.device,
+                &self.camera_bind_group_layout,
+                self.camera_buffer.buffer(),
+            );
+        }
+        let camera_offset = self.camera_buffer.write(
+            &self.queue,
+            bytemuck::bytes_of(&camera.uniform(target_size)),
+            camera_uniform_stride,
         );
 
         let load = if let Some(clear_color) = clear_color {
@@ -335,6 +686,19 @@ impl RenderBuddy {
             wgpu::LoadOp::Load
         };
 
+        let target_view = match target {
+            Some(target) => &self.textures.get(target).unwrap().view,
+            None => &render_context.view,
+        };
+        // Render targets are never multisampled, the surface is resolved from the MSAA texture
+        let (view, resolve_target) = match (target, self.multisampled_texture_handle) {
+            (None, Some(multisampled_texture_handle)) => (
+                &self.textures.get(multisampled_texture_handle).unwrap().view,
+                Some(target_view),
+            ),
+            _ => (target_view, None),
+        };
+
         {
             let mut render_pass =
                 render_context
@@ -342,18 +706,21 @@ impl RenderBuddy {
                     .begin_render_pass(&wgpu::RenderPassDescriptor {
                         label: Some("Render Pass"),
                         color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &render_context.view,
-                            resolve_target: None,
+                            view,
+                            resolve_target,
                             ops: wgpu::Operations { load, store: true },
                         })],
                         depth_stencil_attachment: if use_depth_stencil_attachment {
                             Some(wgpu::RenderPassDepthStencilAttachment {
-                                view: &self.textures.get(self.depth_texture_handle).unwrap().view,
+                                view: &self.textures.get(depth_texture_handle).unwrap().view,
                                 depth_ops: Some(wgpu::Operations {
                                     load: wgpu::LoadOp::Clear(1.0),
                                     store: true,
                                 }),
-                                stencil_ops: None,
+                                stencil_ops: stencil_enabled.then_some(wgpu::Operations {
+                                    load: wgpu::LoadOp::Clear(0),
+                                    store: true,
+                                }),
                             })
                         } else {
                             None
@@ -364,30 +731,163 @@ impl RenderBuddy {
                 &mesh_prepared_batch,
                 &mut render_pass,
                 &self.materials,
-                &camera_bind_group,
+                pipeline_key,
+                target_size,
+                FrameBuffers {
+                    vertex_buffer: self.vertex_buffer.buffer(),
+                    index_buffer: self.index_buffer.buffer(),
+                    camera_bind_group: &self.camera_bind_group,
+                    camera_offset: camera_offset as u32,
+                },
             );
         }
     }
 
+    /// Copies the current frame back from the GPU into an [`Image`]
+    /// Submits everything recorded in the [`RenderContext`] so far,
+    /// so it should be called after the last render of the frame and before [`RenderBuddy::end_frame`]
+    /// Capturing a surface requires [`RenderBuddyDescriptor::frame_capture`] or [`RenderBuddy::set_frame_capture`]
+    pub fn capture_frame(
+        &self,
+        render_context: &mut RenderContext,
+    ) -> Result<Image, RenderBuddyError> {
+        let command_encoder = std::mem::replace(
+            &mut render_context.command_encoder,
+            self.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Render Encoder"),
+                }),
+        );
+        self.queue.submit(std::iter::once(command_encoder.finish()));
+
+        let texture = match &render_context.output {
+            Some(output) => {
+                if !self
+                    .surface_config
+                    .usage
+                    .contains(wgpu::TextureUsages::COPY_SRC)
+                {
+                    return Err(RenderBuddyError::CaptureUnsupported);
+                }
+
+                &output.texture
+            }
+            None => {
+                &self
+                    .textures
+                    .get(
+                        self.headless_texture_handle
+                            .expect("Missing headless texture"),
+                    )
+                    .expect("Missing headless texture")
+                    .texture
+            }
+        };
+
+        self.read_texture(texture)
+    }
+
     /// Presents the frame to WGPU for rendering
+    /// In headless mode the frame is only submitted
     /// Drops the [`RenderContext`]
     pub fn end_frame(&mut self, render_context: RenderContext) {
         self.queue
             .submit(std::iter::once(render_context.command_encoder.finish()));
-        render_context.output.present();
+        if let Some(output) = render_context.output {
+            output.present();
+        }
+
+        self.camera_buffer.clear();
+        self.vertex_buffer.clear();
+        self.index_buffer.clear();
+
+        // Nothing drawn this frame uses the atlases anymore once it's submitted
+        self.evict_font_atlases();
+        for texture_handle in std::mem::take(&mut self.removed_font_textures) {
+            self.remove_texture(texture_handle);
+        }
+        self.frame += 1;
+    }
+
+    /// Lets [`RenderBuddy::capture_frame`] read back the surface, only possible on Vulkan and Dx12
+    /// Dx12 surfaces always allow it and Vulkan drivers practically always do,
+    /// but wgpu can't check it beforehand and fails to configure the surface if the driver doesn't
+    /// Returns whether frames can be captured now, always the case in headless mode
+    pub fn set_frame_capture(&mut self, enabled: bool) -> bool {
+        let Some(surface) = &self.surface else {
+            return true;
+        };
+
+        self.surface_config.usage = surface_usage(self.backend, enabled);
+        if self.surface_config.width > 0 && self.surface_config.height > 0 {
+            surface.configure(&self.device, &self.surface_config);
+        }
+
+        self.surface_config
+            .usage
+            .contains(wgpu::TextureUsages::COPY_SRC)
     }
 
     /// Should be called when the window has been resized
+    /// In headless mode this recreates the offscreen texture at the new size
+    /// A zero sized viewport, e.g. a minimized window, is kept until the next resize
+    /// and [`RenderBuddy::begin`] skips frames in the meantime
     pub fn resize(&mut self, new_surface_size: (u32, u32)) {
         self.surface_config.width = new_surface_size.0;
         self.surface_config.height = new_surface_size.1;
-        self.surface.configure(&self.device, &self.surface_config);
+        if new_surface_size.0 == 0 || new_surface_size.1 == 0 {
+            return;
+        }
+
+        self.configure_surface();
+    }
+
+    /// Configures the surface with the stored config
+    /// and recreates the textures that have to match its size
+    fn configure_surface(&mut self) {
+        let surface_size = (self.surface_config.width, self.surface_config.height);
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.surface_config);
+        }
+
+        if let Some(headless_texture_handle) = self.headless_texture_handle {
+            self.replace_texture(
+                headless_texture_handle,
+                Texture::create_render_target(
+                    &self.device,
+                    surface_size,
+                    self.surface_config.format,
+                    *self
+                        .default_texture_samplers
+                        .get(&TextureSamplerType::Linear)
+                        .unwrap(),
+                ),
+            );
+        }
+
+        if let Some(multisampled_texture_handle) = self.multisampled_texture_handle {
+            self.replace_texture(
+                multisampled_texture_handle,
+                Texture::create_multisampled_texture(
+                    &self.device,
+                    surface_size,
+                    self.surface_config.format,
+                    self.sample_count,
+                    *self
+                        .default_texture_samplers
+                        .get(&TextureSamplerType::Linear)
+                        .unwrap(),
+                ),
+            );
+        }
 
         self.replace_texture(
             self.depth_texture_handle,
             Texture::create_depth_texture(
                 &self.device,
-                &self.surface_config,
+                surface_size,
+                self.depth_format,
+                self.sample_count,
                 *self
                     .default_texture_samplers
                     .get(&TextureSamplerType::Depth)
@@ -397,32 +897,134 @@ impl RenderBuddy {
     }
 }
 
+/// Not every backend can copy out of a surface texture, those that can allow frame captures
+fn surface_usage(backend: wgpu::Backend, frame_capture: bool) -> wgpu::TextureUsages {
+    match backend {
+        wgpu::Backend::Vulkan | wgpu::Backend::Dx12 if frame_capture => {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+        }
+        _ => wgpu::TextureUsages::RENDER_ATTACHMENT,
+    }
+}
+
+/// Whether wgpu accepts render targets of the format with this many samples on the device
+fn sample_count_supported(
+    adapter: &wgpu::Adapter,
+    device: &Device,
+    format: TextureFormat,
+    sample_count: u32,
+) -> bool {
+    // Same features wgpu validates textures with
+    let format_features = if device
+        .features()
+        .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        || !adapter.get_downlevel_capabilities().is_webgpu_compliant()
+    {
+        adapter.get_texture_format_features(format)
+    } else {
+        format.describe().guaranteed_format_features
+    };
+
+    format_features.flags.sample_count_supported(sample_count)
+}
+
+/// Camera uniforms are written at offsets aligned to what the device allows for dynamic offsets
+fn camera_uniform_stride(device: &Device) -> BufferAddress {
+    align_to(
+        CAMERA_UNIFORM_SIZE,
+        device.limits().min_uniform_buffer_offset_alignment as BufferAddress,
+    )
+}
+
+fn create_camera_bind_group(
+    device: &Device,
+    bind_group_layout: &BindGroupLayout,
+    camera_buffer: &Buffer,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: camera_buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(CAMERA_UNIFORM_SIZE),
+            }),
+        }],
+        label: Some("Camera bind group"),
+        layout: bind_group_layout,
+    })
+}
+
+/// The buffers shared by every batch of a render
+struct FrameBuffers<'a> {
+    vertex_buffer: &'a Buffer,
+    index_buffer: &'a Buffer,
+    camera_bind_group: &'a BindGroup,
+    camera_offset: u32,
+}
+
 fn render_prepared_meshes<'a>(
     mesh_batches: &'a Vec<PreparedMeshBatch>,
     render_pass: &mut RenderPass<'a>,
     materials: &'a Arena<Pipeline>,
-    camera_bind_group: &'a BindGroup,
+    pipeline_key: PipelineKey,
+    target_size: (u32, u32),
+    frame_buffers: FrameBuffers<'a>,
 ) {
-    let last_material = ArenaId::default();
+    let mut last_pipeline = None;
+
+    render_pass.set_bind_group(
+        0,
+        frame_buffers.camera_bind_group,
+        &[frame_buffers.camera_offset],
+    );
 
     for mesh_batch in mesh_batches {
-        if mesh_batch.material_handle.id != last_material {
+        let [x, y, width, height] = match mesh_batch.clip {
+            Some(clip) => match RenderBuddy::clip_to_scissor(clip, target_size) {
+                Some(scissor) => scissor,
+                // Entirely clipped
+                None => continue,
+            },
+            None => [0, 0, target_size.0, target_size.1],
+        };
+        render_pass.set_scissor_rect(x, y, width, height);
+
+        let batch_pipeline_key = PipelineKey {
+            stencil_mode: mesh_batch.stencil_mode,
+            ..pipeline_key
+        };
+        if last_pipeline != Some((mesh_batch.material_handle.id, batch_pipeline_key)) {
             let pipeline: &Pipeline = materials
                 .get(mesh_batch.material_handle)
                 .expect("Mesh was given invalid pipeline id");
-            render_pass.set_pipeline(&pipeline.render_pipeline);
-
-            render_pass.set_bind_group(0, &camera_bind_group, &[]); // can probably do this once before the loop
+            render_pass.set_pipeline(pipeline.render_pipeline(batch_pipeline_key));
+            last_pipeline = Some((mesh_batch.material_handle.id, batch_pipeline_key));
         }
+        render_pass.set_stencil_reference(mesh_batch.stencil_reference);
 
-        for (i, bind_group) in mesh_batch.bind_groups.iter().enumerate() {
-            render_pass.set_bind_group(i as u32 + 1, &bind_group, &[]);
+        let bind_groups = mesh_batch
+            .texture_bind_group
+            .as_deref()
+            .into_iter()
+            .chain(mesh_batch.bind_groups.iter());
+        for (i, bind_group) in bind_groups.enumerate() {
+            render_pass.set_bind_group(i as u32 + 1, bind_group, &[]);
         }
 
-        render_pass.set_vertex_buffer(0, mesh_batch.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(
+            0,
+            frame_buffers
+                .vertex_buffer
+                .slice(mesh_batch.vertex_range.clone()),
+        );
         if mesh_batch.indices_len > 0 {
-            render_pass
-                .set_index_buffer(mesh_batch.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.set_index_buffer(
+                frame_buffers
+                    .index_buffer
+                    .slice(mesh_batch.index_range.clone()),
+                wgpu::IndexFormat::Uint32,
+            );
             render_pass.draw_indexed(0..mesh_batch.indices_len, 0, 0..1);
         } else {
             render_pass.draw(0..mesh_batch.vert_len, 0..1);
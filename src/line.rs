@@ -0,0 +1,696 @@
+use std::f32::consts::PI;
+
+use glam::{Vec2, Vec4};
+
+use crate::{
+    arena::Handle,
+    gradient::Gradient,
+    mesh::{Mesh, MeshCreator},
+    pipeline::Pipeline,
+    shape::{segment_count, ShapeGeometry},
+    sprite::Anchor,
+    transform::Transform,
+    RenderBuddy,
+};
+
+/// Joins with a miter longer than this many times the half thickness are drawn beveled instead
+const MITER_LIMIT: f32 = 4.;
+
+/// How two segments of a line are connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    /// Extends the edges until they meet, beveled if that would be too long
+    #[default]
+    Miter,
+    Bevel,
+    Round,
+}
+
+/// How the ends of a line are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    /// Ends exactly at the end points
+    #[default]
+    Butt,
+    /// Extends past the end points by half the thickness
+    Square,
+    Round,
+}
+
+/// Alternating lengths of dashes and gaps, starting with a dash
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DashPattern {
+    pub lengths: Vec<f32>,
+    /// Distance into the pattern the line starts at, useful for animating dashes
+    pub offset: f32,
+}
+
+impl DashPattern {
+    pub fn new(dash: f32, gap: f32) -> Self {
+        Self {
+            lengths: vec![dash, gap],
+            offset: 0.,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Splits the line into the runs of points that are drawn, `None` if nothing is cut out of it
+    fn split(&self, points: &[Vec2], closed: bool) -> Option<Vec<Vec<Vec2>>> {
+        let total: f32 = self.lengths.iter().sum();
+        if total <= 0. || self.lengths.iter().any(|length| *length < 0.) {
+            return None;
+        }
+
+        // Find where in the pattern the line starts
+        let mut index = 0;
+        let mut remaining = self.lengths[0];
+        let mut offset = self.offset.rem_euclid(total);
+        while offset > 0. {
+            if offset < remaining {
+                remaining -= offset;
+                break;
+            }
+            offset -= remaining;
+            index = (index + 1) % self.lengths.len();
+            remaining = self.lengths[index];
+        }
+        let starts_in_dash = index % 2 == 0;
+
+        let mut runs = Vec::new();
+        let mut current_run = Vec::new();
+        let segment_count = if closed {
+            points.len()
+        } else {
+            points.len() - 1
+        };
+        for i in 0..segment_count {
+            let mut from = points[i];
+            let to = points[(i + 1) % points.len()];
+            if index % 2 == 0 && current_run.is_empty() {
+                current_run.push(from);
+            }
+
+            let mut segment_length = from.distance(to);
+            // Dashes ending right on a point end there instead of repeating it at the start of the next segment
+            while segment_length >= remaining {
+                from = from.lerp(to, remaining / segment_length);
+                segment_length -= remaining;
+                // Either ends the current dash or starts the next one
+                current_run.push(from);
+                if index % 2 == 0 {
+                    runs.push(std::mem::take(&mut current_run));
+                }
+                index = (index + 1) % self.lengths.len();
+                remaining = self.lengths[index];
+            }
+            remaining -= segment_length;
+            if index % 2 == 0 {
+                current_run.push(to);
+            }
+        }
+
+        // A dash going past the first point of a closed line continues into the first dash
+        if closed && starts_in_dash && index % 2 == 0 {
+            if runs.is_empty() {
+                return None;
+            }
+            current_run.pop();
+            current_run.append(&mut runs[0]);
+            current_run.dedup_by(|a, b| a.distance_squared(*b) <= f32::EPSILON);
+            runs[0] = current_run;
+        } else if current_run.len() > 1 {
+            runs.push(current_run);
+        }
+
+        Some(runs)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub from: Vec2,
+    pub to: Vec2,
+    pub thickness: f32,
+    pub color: Vec4,
+    /// Used instead of the color when set
+    pub gradient: Option<Gradient>,
+    pub cap: LineCap,
+    pub dash: Option<DashPattern>,
+    pub material: Option<Handle<Pipeline>>,
+}
+
+impl Line {
+    pub fn new(from: Vec2, to: Vec2, thickness: f32, color: Vec4) -> Self {
+        Self {
+            from,
+            to,
+            thickness,
+            color,
+            gradient: None,
+            cap: LineCap::Butt,
+            dash: None,
+            material: None,
+        }
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_dash(mut self, dash: DashPattern) -> Self {
+        self.dash = Some(dash);
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl MeshCreator for Line {
+    fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        let style = StrokeStyle {
+            thickness: self.thickness,
+            join: LineJoin::Miter,
+            cap: self.cap,
+            dash: self.dash.as_ref(),
+        };
+
+        style
+            .stroke(&[self.from, self.to], false, &transform)
+            .into_mesh(
+                self.color,
+                self.gradient.as_ref(),
+                Anchor::Center,
+                self.material,
+                transform,
+                rb,
+            )
+    }
+}
+
+/// A line through several points, points are in the local space of the transform it's pushed with
+#[derive(Debug, Clone)]
+pub struct Polyline {
+    pub points: Vec<Vec2>,
+    pub thickness: f32,
+    pub color: Vec4,
+    /// Used instead of the color when set
+    pub gradient: Option<Gradient>,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Connects the last point back to the first, closed lines have no caps
+    pub closed: bool,
+    pub dash: Option<DashPattern>,
+    pub material: Option<Handle<Pipeline>>,
+}
+
+impl Polyline {
+    pub fn new(points: Vec<Vec2>, thickness: f32, color: Vec4) -> Self {
+        Self {
+            points,
+            thickness,
+            color,
+            gradient: None,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            closed: false,
+            dash: None,
+            material: None,
+        }
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+
+    pub fn with_dash(mut self, dash: DashPattern) -> Self {
+        self.dash = Some(dash);
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl MeshCreator for Polyline {
+    fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        let style = StrokeStyle {
+            thickness: self.thickness,
+            join: self.join,
+            cap: self.cap,
+            dash: self.dash.as_ref(),
+        };
+
+        style
+            .stroke(&self.points, self.closed, &transform)
+            .into_mesh(
+                self.color,
+                self.gradient.as_ref(),
+                Anchor::Center,
+                self.material,
+                transform,
+                rb,
+            )
+    }
+}
+
+/// Everything needed to turn a line into triangles
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StrokeStyle<'a> {
+    pub(crate) thickness: f32,
+    pub(crate) join: LineJoin,
+    pub(crate) cap: LineCap,
+    pub(crate) dash: Option<&'a DashPattern>,
+}
+
+impl StrokeStyle<'_> {
+    pub(crate) fn stroke(
+        &self,
+        points: &[Vec2],
+        closed: bool,
+        transform: &Transform,
+    ) -> ShapeGeometry {
+        let mut points = points.to_vec();
+        points.dedup_by(|a, b| a.distance_squared(*b) <= f32::EPSILON);
+        if closed
+            && points.len() > 1
+            && points[0].distance_squared(points[points.len() - 1]) <= f32::EPSILON
+        {
+            points.pop();
+        }
+
+        let mut geometry = ShapeGeometry::default();
+        if points.len() < 2 || self.thickness <= 0. {
+            return geometry;
+        }
+
+        let half_thickness = self.thickness / 2.;
+        let round_segments = segment_count(None, half_thickness, transform);
+
+        match self.dash.and_then(|dash| dash.split(&points, closed)) {
+            Some(runs) => {
+                for run in runs {
+                    geometry.append(stroke_run(
+                        &run,
+                        false,
+                        half_thickness,
+                        self,
+                        round_segments,
+                    ));
+                }
+            }
+            None => {
+                geometry.append(stroke_run(
+                    &points,
+                    closed,
+                    half_thickness,
+                    self,
+                    round_segments,
+                ));
+            }
+        }
+
+        geometry
+    }
+}
+
+/// Strokes a single run of points without any dashes
+fn stroke_run(
+    points: &[Vec2],
+    closed: bool,
+    half_thickness: f32,
+    style: &StrokeStyle,
+    round_segments: u32,
+) -> ShapeGeometry {
+    let mut geometry = ShapeGeometry::default();
+    if points.len() < 2 {
+        return geometry;
+    }
+
+    let mut points = points.to_vec();
+    let last = points.len() - 1;
+    if !closed && style.cap == LineCap::Square {
+        let start_direction = (points[0] - points[1]).normalize_or_zero();
+        let end_direction = (points[last] - points[last - 1]).normalize_or_zero();
+        points[0] += start_direction * half_thickness;
+        points[last] += end_direction * half_thickness;
+    }
+
+    let joints = (0..points.len())
+        .map(|i| {
+            if !closed && (i == 0 || i == last) {
+                return None;
+            }
+            let previous = points[(i + points.len() - 1) % points.len()];
+            let next = points[(i + 1) % points.len()];
+            Joint::new(previous, points[i], next, half_thickness)
+        })
+        .collect::<Vec<Option<Joint>>>();
+
+    let segment_count = if closed { points.len() } else { last };
+    for i in 0..segment_count {
+        let end = (i + 1) % points.len();
+        let from = points[i];
+        let to = points[end];
+        let normal = (to - from).normalize_or_zero().perp() * half_thickness;
+        let mut corners = [from + normal, to + normal, to - normal, from - normal];
+
+        // Meet the neighbouring segments at the inside of the corners so they don't overlap
+        if let Some(joint) = &joints[i] {
+            corners[if joint.turns_left { 0 } else { 3 }] = joint.inner;
+        }
+        if let Some(joint) = &joints[end] {
+            corners[if joint.turns_left { 1 } else { 2 }] = joint.inner;
+        }
+
+        geometry.append(ShapeGeometry::from_points(
+            corners.to_vec(),
+            vec![0, 1, 2, 0, 2, 3],
+        ));
+    }
+
+    for joint in joints.iter().flatten() {
+        geometry.append(joint.geometry(half_thickness, style.join, round_segments));
+    }
+
+    if !closed && style.cap == LineCap::Round {
+        let start_direction = (points[1] - points[0]).normalize_or_zero();
+        let end_direction = (points[last] - points[last - 1]).normalize_or_zero();
+        for (point, direction) in [(points[0], -start_direction), (points[last], end_direction)] {
+            let normal = direction.perp();
+            geometry.append(fan(
+                point,
+                point,
+                half_thickness,
+                normal.y.atan2(normal.x),
+                -PI,
+                round_segments,
+            ));
+        }
+    }
+
+    geometry
+}
+
+/// A corner between two segments of a line
+struct Joint {
+    point: Vec2,
+    turns_left: bool,
+    /// Where the inner edges of the segments meet
+    inner: Vec2,
+    /// Outer edge of the incoming and outgoing segment at the corner
+    incoming_edge: Vec2,
+    outgoing_edge: Vec2,
+    /// Normals pointing to the outside of the corner
+    incoming_normal: Vec2,
+    outgoing_normal: Vec2,
+    miter_scale: f32,
+}
+
+impl Joint {
+    /// `None` if the segments are parallel and need no join
+    fn new(previous: Vec2, point: Vec2, next: Vec2, half_thickness: f32) -> Option<Self> {
+        let incoming = point - previous;
+        let outgoing = next - point;
+        let turn = incoming
+            .normalize_or_zero()
+            .perp_dot(outgoing.normalize_or_zero());
+        if turn.abs() <= f32::EPSILON {
+            return None;
+        }
+
+        // The outside of the corner is on the right when turning left
+        let turns_left = turn > 0.;
+        let side = if turns_left { -1. } else { 1. };
+        let incoming_normal = incoming.normalize().perp() * side;
+        let outgoing_normal = outgoing.normalize().perp() * side;
+        let miter_direction = (incoming_normal + outgoing_normal).normalize_or_zero();
+        let miter_scale = 1. / miter_direction.dot(incoming_normal);
+
+        // Sharp corners on short segments would put the inner point past the ends of the segments
+        let inner_length = half_thickness * miter_scale;
+        let inner = if miter_scale.is_finite()
+            && inner_length * inner_length
+                <= incoming.length_squared().min(outgoing.length_squared())
+        {
+            point - miter_direction * inner_length
+        } else {
+            point
+        };
+
+        Some(Self {
+            point,
+            turns_left,
+            inner,
+            incoming_edge: point + incoming_normal * half_thickness,
+            outgoing_edge: point + outgoing_normal * half_thickness,
+            incoming_normal,
+            outgoing_normal,
+            miter_scale,
+        })
+    }
+
+    /// Fills the gap on the outside of the corner
+    fn geometry(&self, half_thickness: f32, join: LineJoin, round_segments: u32) -> ShapeGeometry {
+        let bevel = || {
+            ShapeGeometry::from_points(
+                vec![self.inner, self.incoming_edge, self.outgoing_edge],
+                vec![0, 1, 2],
+            )
+        };
+
+        match join {
+            LineJoin::Miter if self.miter_scale.is_finite() && self.miter_scale <= MITER_LIMIT => {
+                let miter_direction = (self.incoming_normal + self.outgoing_normal).normalize();
+                let miter = self.point + miter_direction * half_thickness * self.miter_scale;
+                ShapeGeometry::from_points(
+                    vec![self.inner, self.incoming_edge, miter, self.outgoing_edge],
+                    vec![0, 1, 2, 0, 2, 3],
+                )
+            }
+            LineJoin::Miter | LineJoin::Bevel => bevel(),
+            LineJoin::Round => fan(
+                self.inner,
+                self.point,
+                half_thickness,
+                self.incoming_normal.y.atan2(self.incoming_normal.x),
+                self.incoming_normal.angle_between(self.outgoing_normal),
+                round_segments,
+            ),
+        }
+    }
+}
+
+/// Triangles from `origin` to an arc around `center`
+fn fan(
+    origin: Vec2,
+    center: Vec2,
+    radius: f32,
+    start_angle: f32,
+    sweep: f32,
+    round_segments: u32,
+) -> ShapeGeometry {
+    let segments = ((round_segments as f32 * sweep.abs() / (2. * PI)).ceil() as u32).max(1);
+    let mut points = vec![origin];
+    points.extend((0..=segments).map(|i| {
+        let angle = start_angle + sweep * i as f32 / segments as f32;
+        center + Vec2::new(angle.cos(), angle.sin()) * radius
+    }));
+    let indices = (1..=segments).flat_map(|i| [0, i, i + 1]).collect();
+
+    ShapeGeometry::from_points(points, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(join: LineJoin, cap: LineCap) -> StrokeStyle<'static> {
+        StrokeStyle {
+            thickness: 10.,
+            join,
+            cap,
+            dash: None,
+        }
+    }
+
+    fn triangle_count(geometry: &ShapeGeometry) -> usize {
+        assert!(geometry
+            .indices
+            .iter()
+            .all(|index| (*index as usize) < geometry.points.len()));
+        geometry.indices.len() / 3
+    }
+
+    #[test]
+    fn dashes_continue_across_segments() {
+        let points = [Vec2::ZERO, Vec2::new(10., 0.), Vec2::new(10., 10.)];
+        let runs = DashPattern::new(5., 2.).split(&points, false).unwrap();
+
+        assert_eq!(
+            runs,
+            vec![
+                vec![Vec2::ZERO, Vec2::new(5., 0.)],
+                vec![Vec2::new(7., 0.), Vec2::new(10., 0.), Vec2::new(10., 2.)],
+                vec![Vec2::new(10., 4.), Vec2::new(10., 9.)],
+            ]
+        );
+    }
+
+    #[test]
+    fn dash_ending_on_a_point_isnt_repeated() {
+        let points = [Vec2::ZERO, Vec2::new(10., 0.), Vec2::new(10., 10.)];
+        let runs = DashPattern::new(4., 2.).split(&points, false).unwrap();
+
+        assert_eq!(runs[1], vec![Vec2::new(6., 0.), Vec2::new(10., 0.)]);
+        assert_eq!(runs[2], vec![Vec2::new(10., 2.), Vec2::new(10., 6.)]);
+        assert_eq!(runs.len(), 4);
+    }
+
+    #[test]
+    fn dash_offset_starts_inside_the_pattern() {
+        let points = [Vec2::ZERO, Vec2::new(10., 0.)];
+        let runs = DashPattern::new(5., 2.)
+            .with_offset(3.)
+            .split(&points, false)
+            .unwrap();
+
+        assert_eq!(
+            runs,
+            vec![
+                vec![Vec2::ZERO, Vec2::new(2., 0.)],
+                vec![Vec2::new(4., 0.), Vec2::new(9., 0.)],
+            ]
+        );
+    }
+
+    #[test]
+    fn closed_dashes_continue_past_the_first_point() {
+        let points = [
+            Vec2::ZERO,
+            Vec2::new(10., 0.),
+            Vec2::new(10., 10.),
+            Vec2::new(0., 10.),
+        ];
+        let runs = DashPattern::new(6., 2.)
+            .with_offset(3.)
+            .split(&points, true)
+            .unwrap();
+
+        // The dash starting 3 before the first point ends 3 after it, joined at the corner
+        assert_eq!(runs.len(), 5);
+        let expected = [Vec2::new(0., 3.), Vec2::ZERO, Vec2::new(3., 0.)];
+        assert_eq!(runs[0].len(), expected.len());
+        for (point, expected) in runs[0].iter().zip(expected) {
+            assert!(point.abs_diff_eq(expected, 1e-4));
+        }
+    }
+
+    #[test]
+    fn closed_lines_inside_a_single_dash_arent_split() {
+        let points = [Vec2::ZERO, Vec2::new(10., 0.), Vec2::new(10., 10.)];
+
+        assert_eq!(DashPattern::new(100., 2.).split(&points, true), None);
+        assert!(DashPattern::new(100., 2.)
+            .split(&points, false)
+            .is_some_and(|runs| runs.len() == 1));
+    }
+
+    #[test]
+    fn caps() {
+        let points = [Vec2::ZERO, Vec2::new(100., 0.)];
+        let transform = Transform::default();
+
+        let butt = style(LineJoin::Miter, LineCap::Butt).stroke(&points, false, &transform);
+        assert_eq!(butt.points.len(), 4);
+        assert_eq!(triangle_count(&butt), 2);
+        assert!(butt.min.abs_diff_eq(Vec2::new(0., -5.), 1e-4));
+
+        let square = style(LineJoin::Miter, LineCap::Square).stroke(&points, false, &transform);
+        assert_eq!(square.points.len(), 4);
+        assert!(square.min.abs_diff_eq(Vec2::new(-5., -5.), 1e-4));
+        assert!(square.max.abs_diff_eq(Vec2::new(105., 5.), 1e-4));
+
+        // A half circle fan on each end
+        let round = style(LineJoin::Miter, LineCap::Round).stroke(&points, false, &transform);
+        let fan_segments = (segment_count(None, 5., &transform) as f32 / 2.).ceil() as usize;
+        assert_eq!(round.points.len(), 4 + 2 * (fan_segments + 2));
+        assert_eq!(triangle_count(&round), 2 + 2 * fan_segments);
+        assert!(round.min.x < -4.5 && round.min.x >= -5. - 1e-4);
+    }
+
+    #[test]
+    fn joins() {
+        let points = [Vec2::ZERO, Vec2::new(100., 0.), Vec2::new(100., 100.)];
+        let transform = Transform::default();
+        let segments_points = 8;
+        let segments_triangles = 4;
+
+        let miter = style(LineJoin::Miter, LineCap::Butt).stroke(&points, false, &transform);
+        assert_eq!(miter.points.len(), segments_points + 4);
+        assert_eq!(triangle_count(&miter), segments_triangles + 2);
+        assert!(miter.min.abs_diff_eq(Vec2::new(0., -5.), 1e-4));
+        assert!(miter.max.abs_diff_eq(Vec2::new(105., 100.), 1e-4));
+
+        let bevel = style(LineJoin::Bevel, LineCap::Butt).stroke(&points, false, &transform);
+        assert_eq!(bevel.points.len(), segments_points + 3);
+        assert_eq!(triangle_count(&bevel), segments_triangles + 1);
+
+        // A quarter circle fan on the outside of the corner
+        let round = style(LineJoin::Round, LineCap::Butt).stroke(&points, false, &transform);
+        let fan_segments = (segment_count(None, 5., &transform) as f32 / 4.).ceil() as usize;
+        assert_eq!(round.points.len(), segments_points + fan_segments + 2);
+        assert_eq!(triangle_count(&round), segments_triangles + fan_segments);
+    }
+
+    #[test]
+    fn sharp_miters_are_beveled() {
+        let points = [Vec2::ZERO, Vec2::new(100., 0.), Vec2::new(0., 10.)];
+        let transform = Transform::default();
+
+        let sharp = style(LineJoin::Miter, LineCap::Butt).stroke(&points, false, &transform);
+        assert_eq!(sharp.points.len(), 8 + 3);
+        assert!(sharp.max.x < 100. + 5. * MITER_LIMIT);
+    }
+
+    #[test]
+    fn closed_lines_join_every_point() {
+        let points = [Vec2::ZERO, Vec2::new(100., 0.), Vec2::new(100., 100.)];
+        let transform = Transform::default();
+
+        let closed = style(LineJoin::Bevel, LineCap::Round).stroke(&points, true, &transform);
+        // Caps are ignored on closed lines
+        assert_eq!(closed.points.len(), 3 * 4 + 3 * 3);
+        assert_eq!(triangle_count(&closed), 3 * 2 + 3);
+    }
+}
@@ -1,12 +1,13 @@
 use std::{collections::BTreeSet, fmt::Debug};
 
 use wgpu::{
-    include_wgsl, BindGroup, BindGroupLayout, Device, PrimitiveTopology, RenderPipeline,
-    ShaderModuleDescriptor,
+    include_wgsl, BindGroup, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation,
+    BlendState, Device, PrimitiveTopology, RenderPipeline, ShaderModuleDescriptor,
 };
 
 use crate::{
     arena::{ArenaId, Handle},
+    errors::RenderBuddyError,
     mesh::{Mesh, MeshAttribute},
     pipeline::Pipeline,
     RenderBuddy,
@@ -14,12 +15,170 @@ use crate::{
 
 pub type MaterialHandle = ArenaId;
 
+/// Presets for how a material's output is combined with what is already in the target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendMode {
+    /// Regular transparency for straight alpha colors
+    #[default]
+    Alpha,
+    /// Transparency for colors that are already multiplied by their alpha
+    PremultipliedAlpha,
+    /// Adds the color on top, useful for particles and lights
+    Additive,
+    /// Darkens the target by the color, useful for shadows
+    /// Expects premultiplied output, [`DefaultMat`] premultiplies its colors for it
+    Multiply,
+    /// Brightens the target by the inverse of the color
+    /// Expects premultiplied output, [`DefaultMat`] premultiplies its colors for it
+    Screen,
+    /// Overwrites the target, ignoring alpha
+    Opaque,
+}
+
+impl BlendMode {
+    /// Straight alpha can't be blended this way with fixed blend factors,
+    /// so the shader has to multiply the color by its alpha first
+    pub fn requires_premultiplied(&self) -> bool {
+        matches!(self, BlendMode::Multiply | BlendMode::Screen)
+    }
+}
+
+impl From<BlendMode> for BlendState {
+    fn from(blend_mode: BlendMode) -> Self {
+        match blend_mode {
+            BlendMode::Alpha => BlendState::ALPHA_BLENDING,
+            BlendMode::PremultipliedAlpha => BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            },
+            BlendMode::Multiply => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::Dst,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+            BlendMode::Screen => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::OneMinusSrc,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+            BlendMode::Opaque => BlendState::REPLACE,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DefaultMat {
+    pub blend_mode: BlendMode,
+}
+
+impl DefaultMat {
+    /// The default sprite material drawn with a different blend mode,
+    /// push it with [`RenderBuddy::push_material`] and set it on meshes to use it
+    pub fn with_blend_mode(blend_mode: BlendMode) -> Self {
+        Self { blend_mode }
+    }
+}
+
+impl Material for DefaultMat {
+    fn shader(&self) -> ShaderModuleDescriptor<'_> {
+        if self.blend_mode.requires_premultiplied() {
+            include_wgsl!("./default_shaders/premultiplied.wgsl")
+        } else {
+            include_wgsl!("./default_shaders/default.wgsl")
+        }
+    }
+
+    fn blend_state(&self) -> Option<BlendState> {
+        Some(self.blend_mode.into())
+    }
+}
+
+/// The default material for stencil masks, discards mostly transparent pixels
+/// so masks take the shape of their texture instead of their quad
+#[derive(Debug)]
+pub struct MaskMat {}
+impl Material for MaskMat {
+    fn shader(&self) -> ShaderModuleDescriptor<'_> {
+        include_wgsl!("./default_shaders/mask.wgsl")
+    }
+
+    fn label(&self) -> &str {
+        "Mask Material"
+    }
+}
+
+/// The default material for rounded and bordered rects,
+/// draws the shape with a signed distance field so every rect stays a single quad
+#[derive(Debug)]
+pub struct RoundedRectMat {}
+impl Material for RoundedRectMat {
+    fn shader(&self) -> ShaderModuleDescriptor<'_> {
+        include_wgsl!("./default_shaders/rounded_rect.wgsl")
+    }
+
+    fn vertex_attributes(&self) -> BTreeSet<MeshAttribute> {
+        BTreeSet::from([
+            MeshAttribute::Position,
+            MeshAttribute::UV,
+            MeshAttribute::Color,
+            MeshAttribute::Custom(0),
+            MeshAttribute::Custom(1),
+            MeshAttribute::Custom(2),
+            MeshAttribute::Custom(3),
+        ])
+    }
+
+    fn has_texture(&self) -> bool {
+        false
+    }
+
+    fn label(&self) -> &str {
+        "Rounded Rect Material"
+    }
+}
+
+/// The default material for SDF text, draws glyphs from their distance field with an optional outline and shadow
 #[derive(Debug)]
-pub struct DefaultMat {}
-impl Material for DefaultMat {}
+pub struct SdfTextMat {}
+impl Material for SdfTextMat {
+    fn shader(&self) -> ShaderModuleDescriptor<'_> {
+        include_wgsl!("./default_shaders/sdf_text.wgsl")
+    }
+
+    fn vertex_attributes(&self) -> BTreeSet<MeshAttribute> {
+        BTreeSet::from([
+            MeshAttribute::Position,
+            MeshAttribute::UV,
+            MeshAttribute::Color,
+            MeshAttribute::Custom(0),
+            MeshAttribute::Custom(1),
+            MeshAttribute::Custom(2),
+            MeshAttribute::Custom(3),
+        ])
+    }
+
+    fn label(&self) -> &str {
+        "SDF Text Material"
+    }
+}
 
 pub trait Material: Debug {
-    fn shader(&self) -> ShaderModuleDescriptor {
+    fn shader(&self) -> ShaderModuleDescriptor<'_> {
         include_wgsl!("./default_shaders/default.wgsl")
     }
 
@@ -31,15 +190,15 @@ pub trait Material: Debug {
         ])
     }
 
-    fn get_bind_group_layouts(&self, device: &Device) -> Vec<BindGroupLayout> {
+    fn get_bind_group_layouts(&self, _device: &Device) -> Vec<BindGroupLayout> {
         Vec::default()
     }
 
     fn get_bind_groups(
         &self,
-        mesh: &Mesh,
-        rb: &RenderBuddy,
-        render_pipeline: &RenderPipeline,
+        _mesh: &Mesh,
+        _rb: &RenderBuddy,
+        _render_pipeline: &RenderPipeline,
     ) -> Vec<BindGroup> {
         Vec::default()
     }
@@ -56,6 +215,11 @@ pub trait Material: Debug {
         false
     }
 
+    /// How the fragment output is blended into the target, `None` writes it as is
+    fn blend_state(&self) -> Option<BlendState> {
+        Some(BlendMode::Alpha.into())
+    }
+
     fn filterable_texture(&self) -> bool {
         true
     }
@@ -66,12 +230,12 @@ pub trait Material: Debug {
 }
 
 impl RenderBuddy {
-    pub fn push_material(&mut self, material: impl Material + 'static) -> Handle<Pipeline> {
-        let pipeline = Pipeline {
-            render_pipeline: self.create_pipeline_from_material(&material),
-            material: Box::from(material),
-        };
-
-        self.materials.insert(pipeline)
+    /// Builds the material's pipeline and returns a handle meshes can use to reference it
+    /// Fails if the shader or pipeline doesn't pass validation
+    pub fn push_material(
+        &mut self,
+        material: impl Material + 'static,
+    ) -> Result<Handle<Pipeline>, RenderBuddyError> {
+        self.insert_material(Box::from(material))
     }
 }
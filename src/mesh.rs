@@ -7,12 +7,14 @@ use wgpu::{VertexAttribute, VertexFormat};
 use crate::{
     arena::{ArenaId, Handle},
     pipeline::Pipeline,
+    rect::Rect,
+    stencil::StencilMode,
     texture::Texture,
     transform::Transform,
     RenderBuddy,
 };
 
-pub const QUAD_INDICES: [u16; 6] = [0, 2, 3, 0, 1, 2];
+pub const QUAD_INDICES: [u32; 6] = [0, 2, 3, 0, 1, 2];
 
 pub const QUAD_VERTEX_POSITIONS: [Vec2; 4] = [
     Vec2::new(-0.5, -0.5),
@@ -118,7 +120,16 @@ pub struct MeshBuilder {
     pub(crate) texture_handle: Option<Handle<Texture>>,
     pub(crate) material_handle: Handle<Pipeline>,
     pub(crate) vertices: Vec<Vertex>,
-    pub(crate) indices: Vec<u16>,
+    pub(crate) indices: Vec<u32>,
+    pub(crate) clip: Option<Rect>,
+    pub(crate) stencil_mode: StencilMode,
+    pub(crate) stencil_reference: u32,
+}
+
+impl Default for MeshBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MeshBuilder {
@@ -128,6 +139,9 @@ impl MeshBuilder {
             material_handle: Handle::new(ArenaId::first()), // TODO(loui): we should really ref the default material but its hard without a ref to rb
             vertices: Vec::default(),
             indices: Vec::default(),
+            clip: None,
+            stencil_mode: StencilMode::Disabled,
+            stencil_reference: 0,
         }
     }
 
@@ -182,9 +196,8 @@ impl MeshBuilder {
                 .map(|v| Vertex::new().with_attribute(attribute, *v))
                 .collect();
         } else {
-            for i in 0..values.len() {
-                let vert = &mut self.vertices[i];
-                vert.set_attribute(attribute, values[i]);
+            for (vert, value) in self.vertices.iter_mut().zip(values) {
+                vert.set_attribute(attribute, value);
             }
         }
 
@@ -200,7 +213,7 @@ impl MeshBuilder {
         self
     }
 
-    pub fn with_indices(mut self, indices: &[u16]) -> Self {
+    pub fn with_indices(mut self, indices: &[u32]) -> Self {
         self.indices = indices.into();
 
         self
@@ -218,6 +231,21 @@ impl MeshBuilder {
         self
     }
 
+    /// Clips the mesh to the rect, see [`RenderBuddy::push_clip`]
+    pub fn with_clip(mut self, clip: Rect) -> Self {
+        self.clip = Some(clip);
+
+        self
+    }
+
+    /// Sets how the mesh uses the stencil buffer, see [`RenderBuddy::set_stencil`]
+    pub fn with_stencil(mut self, stencil_mode: StencilMode, stencil_reference: u32) -> Self {
+        self.stencil_mode = stencil_mode;
+        self.stencil_reference = stencil_reference;
+
+        self
+    }
+
     pub fn build(self) -> Mesh {
         Mesh {
             texture_handle: self.texture_handle,
@@ -225,6 +253,9 @@ impl MeshBuilder {
             vertices: self.vertices,
             indices: self.indices,
             z: 0.,
+            clip: self.clip,
+            stencil_mode: self.stencil_mode,
+            stencil_reference: self.stencil_reference,
         }
     }
 }
@@ -234,16 +265,21 @@ pub struct Mesh {
     pub(crate) texture_handle: Option<Handle<Texture>>,
     pub(crate) material_handle: Handle<Pipeline>,
     pub(crate) vertices: Vec<Vertex>,
-    pub(crate) indices: Vec<u16>,
+    pub(crate) indices: Vec<u32>,
     // used for sorting
     pub(crate) z: f32,
+    /// Clip rect in target pixels, meshes pushed without one use the current clip
+    pub(crate) clip: Option<Rect>,
+    /// Meshes pushed with [`StencilMode::Disabled`] use the current stencil mode
+    pub(crate) stencil_mode: StencilMode,
+    pub(crate) stencil_reference: u32,
 }
 impl Mesh {
     pub fn new(
         texture_handle: Option<Handle<Texture>>,
         material_handle: Handle<Pipeline>,
         vertices: Vec<Vertex>,
-        indices: Vec<u16>,
+        indices: Vec<u32>,
         z: f32,
     ) -> Self {
         Self {
@@ -252,10 +288,26 @@ impl Mesh {
             vertices,
             indices,
             z,
+            clip: None,
+            stencil_mode: StencilMode::Disabled,
+            stencil_reference: 0,
         }
     }
 
-    pub fn concat(&mut self, mut vertices: Vec<Vertex>, mut indices: Vec<u16>) {
+    /// Clips the mesh to the rect, see [`RenderBuddy::push_clip`]
+    pub fn with_clip(mut self, clip: Rect) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Sets how the mesh uses the stencil buffer, see [`RenderBuddy::set_stencil`]
+    pub fn with_stencil(mut self, stencil_mode: StencilMode, stencil_reference: u32) -> Self {
+        self.stencil_mode = stencil_mode;
+        self.stencil_reference = stencil_reference;
+        self
+    }
+
+    pub fn concat(&mut self, mut vertices: Vec<Vertex>, mut indices: Vec<u32>) {
         self.vertices.append(&mut vertices);
         self.indices.append(&mut indices);
     }
@@ -267,6 +319,9 @@ pub enum MeshAttribute {
     UV,
     Color,
     Normal,
+    /// Extra per vertex data for custom materials, passed to the shader as a `vec4<f32>`
+    /// Custom attributes come after the built in ones, ordered by their index
+    Custom(u8),
 }
 
 impl MeshAttribute {
@@ -276,6 +331,7 @@ impl MeshAttribute {
             MeshAttribute::UV => mem::size_of::<[f32; 2]>(),
             MeshAttribute::Color => mem::size_of::<[f32; 4]>(),
             MeshAttribute::Normal => mem::size_of::<[f32; 2]>(),
+            MeshAttribute::Custom(_) => mem::size_of::<[f32; 4]>(),
         }
     }
 
@@ -285,6 +341,7 @@ impl MeshAttribute {
             MeshAttribute::UV => VertexFormat::Float32x2,
             MeshAttribute::Color => VertexFormat::Float32x4,
             MeshAttribute::Normal => VertexFormat::Float32x2,
+            MeshAttribute::Custom(_) => VertexFormat::Float32x4,
         }
     }
 }
@@ -295,15 +352,18 @@ pub enum AttributeValue {
     UV([f32; 2]),
     Color([f32; 4]),
     Normal([f32; 2]),
+    Custom(u8, [f32; 4]),
 }
 
 impl AttributeValue {
+    #[allow(dead_code, clippy::wrong_self_convention)]
     fn into_mesh_attr(&self) -> MeshAttribute {
         match self {
             AttributeValue::Position(_) => MeshAttribute::Position,
             AttributeValue::UV(_) => MeshAttribute::UV,
             AttributeValue::Color(_) => MeshAttribute::Color,
             AttributeValue::Normal(_) => MeshAttribute::Normal,
+            AttributeValue::Custom(index, _) => MeshAttribute::Custom(*index),
         }
     }
 
@@ -313,6 +373,7 @@ impl AttributeValue {
             AttributeValue::UV(values) => cast_slice(values),
             AttributeValue::Color(values) => cast_slice(values),
             AttributeValue::Normal(values) => cast_slice(values),
+            AttributeValue::Custom(_, values) => cast_slice(values),
         }
     }
 }
@@ -339,6 +400,12 @@ pub fn get_attribute_layout<'a>(
 #[derive(Clone, Debug)]
 pub struct Vertex(pub BTreeMap<MeshAttribute, AttributeValue>);
 
+impl Default for Vertex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Vertex {
     pub fn new() -> Self {
         Self(BTreeMap::new())
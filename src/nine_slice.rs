@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use glam::Vec2;
+
+use crate::{
+    arena::Handle,
+    mesh::{AttributeValue, Mesh, MeshAttribute, MeshCreator, Vertex, QUAD_INDICES},
+    pipeline::Pipeline,
+    rect::Rect,
+    sprite::Anchor,
+    texture::Texture,
+    transform::Transform,
+    RenderBuddy,
+};
+
+/// How the edges and center of a nine slice fill the space between the corners
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SliceScaleMode {
+    #[default]
+    Stretch,
+    /// Repeats the slice at its texel size, cutting off the last repeat
+    Tile,
+}
+
+/// Size of the borders of a nine slice texture in texels
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SliceInsets {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl SliceInsets {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    /// The same inset on every side
+    pub fn uniform(inset: f32) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+}
+
+/// A texture split into 9 parts by its insets, drawn at any size with the corners kept at their texel size
+/// The corners are scaled down if the size is too small to fit them
+#[derive(Clone, Copy, Debug)]
+pub struct NineSlice {
+    pub handle: Handle<Texture>,
+    pub insets: SliceInsets,
+    pub size: Vec2,
+    /// Part of the texture to slice, in texels, the whole texture when `None`
+    pub texture_rect: Option<Rect>,
+    pub edge_mode: SliceScaleMode,
+    pub center_mode: SliceScaleMode,
+    pub color: [f32; 4],
+    pub anchor: Anchor,
+    pub material: Option<Handle<Pipeline>>,
+}
+
+impl NineSlice {
+    pub fn new(handle: Handle<Texture>, insets: SliceInsets, size: Vec2) -> Self {
+        Self {
+            handle,
+            insets,
+            size,
+            texture_rect: None,
+            edge_mode: SliceScaleMode::Stretch,
+            center_mode: SliceScaleMode::Stretch,
+            color: [1., 1., 1., 1.],
+            anchor: Anchor::Center,
+            material: None,
+        }
+    }
+
+    pub fn with_texture_rect(mut self, texture_rect: Rect) -> Self {
+        self.texture_rect = Some(texture_rect);
+        self
+    }
+
+    pub fn with_edge_mode(mut self, edge_mode: SliceScaleMode) -> Self {
+        self.edge_mode = edge_mode;
+        self
+    }
+
+    pub fn with_center_mode(mut self, center_mode: SliceScaleMode) -> Self {
+        self.center_mode = center_mode;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl MeshCreator for NineSlice {
+    fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        let texture = rb
+            .textures
+            .get(self.handle)
+            .expect("Mesh is missing texture");
+        let texture_size = texture.dimensions;
+        let region = self
+            .texture_rect
+            .unwrap_or(Rect::from_corners(Vec2::ZERO, texture_size));
+
+        let columns = SliceAxis::new(
+            self.size.x,
+            self.insets.left,
+            self.insets.right,
+            region.min.x,
+            region.max.x,
+        );
+        // Rows go up from the bottom while the texture goes down from the top
+        let rows = SliceAxis::new(
+            self.size.y,
+            self.insets.bottom,
+            self.insets.top,
+            region.max.y,
+            region.min.y,
+        );
+
+        let offset = (self.anchor.as_vec() + 0.5) * self.size;
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for row in 0..3 {
+            for column in 0..3 {
+                let mode = if row == 1 && column == 1 {
+                    self.center_mode
+                } else {
+                    self.edge_mode
+                };
+                for (x, u) in columns.segments(column, mode) {
+                    for (y, v) in rows.segments(row, mode) {
+                        let corners = [
+                            (Vec2::new(x.0, y.0), Vec2::new(u.0, v.0)),
+                            (Vec2::new(x.1, y.0), Vec2::new(u.1, v.0)),
+                            (Vec2::new(x.1, y.1), Vec2::new(u.1, v.1)),
+                            (Vec2::new(x.0, y.1), Vec2::new(u.0, v.1)),
+                        ];
+
+                        let vertex_offset = vertices.len() as u32;
+                        vertices.extend(corners.map(|(position, uv)| {
+                            let position =
+                                transform.transform_point((position - offset).extend(0.));
+                            Vertex(BTreeMap::from([
+                                (
+                                    MeshAttribute::Position,
+                                    AttributeValue::Position(position.into()),
+                                ),
+                                (
+                                    MeshAttribute::UV,
+                                    AttributeValue::UV((uv / texture_size).into()),
+                                ),
+                                (MeshAttribute::Color, AttributeValue::Color(self.color)),
+                            ]))
+                        }));
+                        indices.extend(QUAD_INDICES.map(|index| index + vertex_offset));
+                    }
+                }
+            }
+        }
+
+        Mesh::new(
+            Some(self.handle),
+            self.material.unwrap_or(rb.material_map.default),
+            vertices,
+            indices,
+            transform.position.z,
+        )
+    }
+}
+
+/// Where the slices start and end along one axis, in local space and in texels
+struct SliceAxis {
+    positions: [f32; 4],
+    texels: [f32; 4],
+}
+
+impl SliceAxis {
+    fn new(size: f32, start_inset: f32, end_inset: f32, texel_start: f32, texel_end: f32) -> Self {
+        let direction = (texel_end - texel_start).signum();
+        // Shrink the borders when they don't fit
+        let inset_scale = (size / (start_inset + end_inset)).min(1.);
+        let (start, end) = if inset_scale.is_finite() {
+            (start_inset * inset_scale, end_inset * inset_scale)
+        } else {
+            (0., 0.)
+        };
+
+        Self {
+            positions: [0., start, size - end, size],
+            texels: [
+                texel_start,
+                texel_start + start_inset * direction,
+                texel_end - end_inset * direction,
+                texel_end,
+            ],
+        }
+    }
+
+    /// Position and texel ranges of the quads making up a slice
+    fn segments(&self, slice: usize, mode: SliceScaleMode) -> Vec<((f32, f32), (f32, f32))> {
+        let positions = (self.positions[slice], self.positions[slice + 1]);
+        let texels = (self.texels[slice], self.texels[slice + 1]);
+        let texel_length = (texels.1 - texels.0).abs();
+        if slice != 1 || mode == SliceScaleMode::Stretch || texel_length <= 0. {
+            return vec![(positions, texels)];
+        }
+
+        let mut segments = Vec::new();
+        let mut start = positions.0;
+        while start < positions.1 {
+            let end = (start + texel_length).min(positions.1);
+            let fraction = (end - start) / texel_length;
+            segments.push((
+                (start, end),
+                (texels.0, texels.0 + (texels.1 - texels.0) * fraction),
+            ));
+            start = end;
+        }
+
+        segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insets_shrink_when_they_dont_fit() {
+        let axis = SliceAxis::new(10., 8., 12., 0., 100.);
+
+        assert_eq!(axis.positions, [0., 4., 4., 10.]);
+        // The texels still cover the whole border
+        assert_eq!(axis.texels, [0., 8., 88., 100.]);
+    }
+
+    #[test]
+    fn zero_insets() {
+        let axis = SliceAxis::new(50., 0., 0., 0., 32.);
+        assert_eq!(axis.positions, [0., 0., 50., 50.]);
+
+        // Without size or insets the scale is NaN, the borders collapse instead
+        let empty = SliceAxis::new(0., 0., 0., 0., 32.);
+        assert_eq!(empty.positions, [0.; 4]);
+    }
+
+    #[test]
+    fn flipped_texels_go_backwards() {
+        let axis = SliceAxis::new(100., 10., 20., 64., 0.);
+
+        assert_eq!(axis.texels, [64., 54., 20., 0.]);
+    }
+
+    #[test]
+    fn tiling_cuts_off_the_last_tile() {
+        let axis = SliceAxis::new(95., 10., 10., 0., 40.);
+
+        let segments = axis.segments(1, SliceScaleMode::Tile);
+        assert_eq!(
+            segments,
+            vec![
+                ((10., 30.), (10., 30.)),
+                ((30., 50.), (10., 30.)),
+                ((50., 70.), (10., 30.)),
+                ((70., 85.), (10., 25.)),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_the_middle_slice_tiles() {
+        let axis = SliceAxis::new(95., 10., 10., 0., 40.);
+
+        assert_eq!(
+            axis.segments(0, SliceScaleMode::Tile),
+            vec![((0., 10.), (0., 10.))]
+        );
+        assert_eq!(
+            axis.segments(1, SliceScaleMode::Stretch),
+            vec![((10., 85.), (10., 30.))]
+        );
+    }
+}
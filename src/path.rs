@@ -0,0 +1,501 @@
+use std::f32::consts::{PI, TAU};
+
+use glam::{Vec2, Vec4};
+
+use crate::{
+    arena::Handle,
+    gradient::Gradient,
+    line::{DashPattern, LineCap, LineJoin, StrokeStyle},
+    mesh::{Mesh, MeshCreator},
+    pipeline::Pipeline,
+    shape::{segment_count, ShapeGeometry, ShapeMode, CURVE_TOLERANCE, MAX_SEGMENTS},
+    sprite::Anchor,
+    transform::Transform,
+    RenderBuddy,
+};
+
+/// Decides which parts of a path are inside it when it's filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    /// Inside wherever the outlines wind around a point a non zero number of times,
+    /// so overlapping sub paths in the same direction are filled and opposite ones cut holes
+    #[default]
+    NonZero,
+    /// Inside wherever a point is enclosed an odd number of times, so every nested sub path cuts a hole
+    EvenOdd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PathCommand {
+    MoveTo(Vec2),
+    LineTo(Vec2),
+    QuadTo(Vec2, Vec2),
+    CubicTo(Vec2, Vec2, Vec2),
+    Arc {
+        center: Vec2,
+        radius: f32,
+        start_angle: f32,
+        sweep: f32,
+    },
+    Close,
+}
+
+/// A vector shape made of lines and curves, either filled or stroked
+/// Points are in the local space of the transform it's pushed with,
+/// curves are flattened so they stay smooth at the size they're drawn at
+#[derive(Debug, Clone)]
+pub struct Path {
+    commands: Vec<PathCommand>,
+    /// Where the next command starts from
+    current: Vec2,
+    /// Where the current sub path started, closing it goes back here
+    start: Vec2,
+    pub color: Vec4,
+    /// Used instead of the color when set
+    pub gradient: Option<Gradient>,
+    pub mode: ShapeMode,
+    pub fill_rule: FillRule,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    pub dash: Option<DashPattern>,
+    pub material: Option<Handle<Pipeline>>,
+}
+
+impl Path {
+    /// An empty filled path, drawing without calling [`Path::move_to`] first starts at the origin
+    pub fn new(color: Vec4) -> Self {
+        Self {
+            commands: Vec::new(),
+            current: Vec2::ZERO,
+            start: Vec2::ZERO,
+            color,
+            gradient: None,
+            mode: ShapeMode::Fill,
+            fill_rule: FillRule::NonZero,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            dash: None,
+            material: None,
+        }
+    }
+
+    /// Starts a new sub path
+    pub fn move_to(mut self, point: Vec2) -> Self {
+        self.commands.push(PathCommand::MoveTo(point));
+        self.current = point;
+        self.start = point;
+        self
+    }
+
+    pub fn line_to(mut self, point: Vec2) -> Self {
+        self.commands.push(PathCommand::LineTo(point));
+        self.current = point;
+        self
+    }
+
+    /// Quadratic Bézier curve bending towards `control`
+    pub fn quad_to(mut self, control: Vec2, point: Vec2) -> Self {
+        self.commands.push(PathCommand::QuadTo(control, point));
+        self.current = point;
+        self
+    }
+
+    /// Cubic Bézier curve, leaving towards `control1` and arriving from `control2`
+    pub fn cubic_to(mut self, control1: Vec2, control2: Vec2, point: Vec2) -> Self {
+        self.commands
+            .push(PathCommand::CubicTo(control1, control2, point));
+        self.current = point;
+        self
+    }
+
+    /// Rounds the corner at `corner` between the current point and `point` with an arc of the given radius,
+    /// like the canvas `arcTo`, the path ends where the arc meets the line towards `point`
+    pub fn arc_to(mut self, corner: Vec2, point: Vec2, radius: f32) -> Self {
+        let incoming = (self.current - corner).normalize_or_zero();
+        let outgoing = (point - corner).normalize_or_zero();
+        let angle = incoming.angle_between(outgoing).abs();
+        // Straight lines and reversals have no corner to round
+        if radius <= 0.
+            || incoming == Vec2::ZERO
+            || outgoing == Vec2::ZERO
+            || angle >= PI - 1e-4
+            || angle <= 1e-4
+        {
+            return self.line_to(corner);
+        }
+
+        let tangent_distance = radius / (angle / 2.).tan();
+        let arc_start = corner + incoming * tangent_distance;
+        let arc_end = corner + outgoing * tangent_distance;
+        let center = corner + (incoming + outgoing).normalize() * radius / (angle / 2.).sin();
+
+        let start_angle = (arc_start - center).y.atan2((arc_start - center).x);
+        let end_angle = (arc_end - center).y.atan2((arc_end - center).x);
+        // The arc is always the short way around, less than half a turn
+        let sweep = (end_angle - start_angle + PI).rem_euclid(TAU) - PI;
+
+        self = self.line_to(arc_start);
+        self.commands.push(PathCommand::Arc {
+            center,
+            radius,
+            start_angle,
+            sweep,
+        });
+        self.current = arc_end;
+        self
+    }
+
+    /// Connects the current sub path back to where it started
+    pub fn close(mut self) -> Self {
+        self.commands.push(PathCommand::Close);
+        self.current = self.start;
+        self
+    }
+
+    pub fn with_stroke(mut self, thickness: f32) -> Self {
+        self.mode = ShapeMode::Stroke(thickness);
+        self
+    }
+
+    pub fn with_fill_rule(mut self, fill_rule: FillRule) -> Self {
+        self.fill_rule = fill_rule;
+        self
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn with_dash(mut self, dash: DashPattern) -> Self {
+        self.dash = Some(dash);
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    /// Turns the path into sub paths of straight segments, no further than `tolerance` from the curves
+    fn flatten(&self, tolerance: f32, transform: &Transform) -> Vec<SubPath> {
+        let mut sub_paths = Vec::new();
+        let mut points: Vec<Vec2> = Vec::new();
+        let mut current = Vec2::ZERO;
+        let mut start = Vec2::ZERO;
+
+        let finish = |points: &mut Vec<Vec2>, closed: bool, sub_paths: &mut Vec<SubPath>| {
+            if points.len() > 1 {
+                sub_paths.push(SubPath {
+                    points: std::mem::take(points),
+                    closed,
+                });
+            }
+            points.clear();
+        };
+
+        for command in &self.commands {
+            if points.is_empty() && !matches!(command, PathCommand::MoveTo(_) | PathCommand::Close)
+            {
+                points.push(current);
+                start = current;
+            }
+
+            match *command {
+                PathCommand::MoveTo(point) => {
+                    finish(&mut points, false, &mut sub_paths);
+                    current = point;
+                    start = point;
+                }
+                PathCommand::LineTo(point) => {
+                    points.push(point);
+                    current = point;
+                }
+                PathCommand::QuadTo(control, point) => {
+                    let deviation = (current - 2. * control + point).length();
+                    let segments = curve_segments(deviation / 4., tolerance);
+                    points.extend((1..=segments).map(|i| {
+                        let t = i as f32 / segments as f32;
+                        current.lerp(control, t).lerp(control.lerp(point, t), t)
+                    }));
+                    current = point;
+                }
+                PathCommand::CubicTo(control1, control2, point) => {
+                    let deviation = (current - 2. * control1 + control2)
+                        .length()
+                        .max((control1 - 2. * control2 + point).length());
+                    let segments = curve_segments(deviation * 3. / 4., tolerance);
+                    points.extend((1..=segments).map(|i| {
+                        let t = i as f32 / segments as f32;
+                        let a = current.lerp(control1, t);
+                        let b = control1.lerp(control2, t);
+                        let c = control2.lerp(point, t);
+                        a.lerp(b, t).lerp(b.lerp(c, t), t)
+                    }));
+                    current = point;
+                }
+                PathCommand::Arc {
+                    center,
+                    radius,
+                    start_angle,
+                    sweep,
+                } => {
+                    let full_segments = segment_count(None, radius, transform);
+                    let segments =
+                        ((full_segments as f32 * sweep.abs() / TAU).ceil() as u32).max(1);
+                    points.extend((1..=segments).map(|i| {
+                        let angle = start_angle + sweep * i as f32 / segments as f32;
+                        center + Vec2::new(angle.cos(), angle.sin()) * radius
+                    }));
+                    current = *points.last().unwrap();
+                }
+                PathCommand::Close => {
+                    finish(&mut points, true, &mut sub_paths);
+                    current = start;
+                }
+            }
+        }
+        finish(&mut points, false, &mut sub_paths);
+
+        sub_paths
+    }
+}
+
+impl MeshCreator for Path {
+    fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        let scale = transform.scale.x.abs().max(transform.scale.y.abs());
+        let tolerance = CURVE_TOLERANCE / scale.max(f32::EPSILON);
+        let sub_paths = self.flatten(tolerance, &transform);
+
+        let geometry = match self.mode {
+            ShapeMode::Fill => fill(&sub_paths, self.fill_rule),
+            ShapeMode::Stroke(thickness) => {
+                let style = StrokeStyle {
+                    thickness,
+                    join: self.join,
+                    cap: self.cap,
+                    dash: self.dash.as_ref(),
+                };
+                let mut geometry = ShapeGeometry::default();
+                for sub_path in &sub_paths {
+                    geometry.append(style.stroke(&sub_path.points, sub_path.closed, &transform));
+                }
+                geometry
+            }
+        };
+
+        geometry.into_mesh(
+            self.color,
+            self.gradient.as_ref(),
+            Anchor::Center,
+            self.material,
+            transform,
+            rb,
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+struct SubPath {
+    points: Vec<Vec2>,
+    closed: bool,
+}
+
+/// How many segments a curve needs so it's never further than `tolerance` from them,
+/// `deviation` scales how far the curve bends away from a straight line
+fn curve_segments(deviation: f32, tolerance: f32) -> u32 {
+    ((deviation / tolerance).sqrt().ceil() as u32).clamp(1, MAX_SEGMENTS)
+}
+
+/// Edge of a filled path going up, `winding` is -1 when it was drawn going down
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    bottom: Vec2,
+    top: Vec2,
+    winding: i32,
+}
+
+impl Edge {
+    fn x_at(&self, y: f32) -> f32 {
+        self.bottom.x
+            + (y - self.bottom.y) / (self.top.y - self.bottom.y) * (self.top.x - self.bottom.x)
+    }
+
+    /// Height at which the edges cross, if they do between their ends
+    fn crossing(&self, other: &Edge) -> Option<f32> {
+        let direction = self.top - self.bottom;
+        let other_direction = other.top - other.bottom;
+        let denominator = direction.perp_dot(other_direction);
+        if denominator == 0. {
+            return None;
+        }
+
+        let offset = other.bottom - self.bottom;
+        let t = offset.perp_dot(other_direction) / denominator;
+        let u = offset.perp_dot(direction) / denominator;
+        (t > 0. && t < 1. && u > 0. && u < 1.).then_some(self.bottom.y + direction.y * t)
+    }
+}
+
+/// Fills the closed sub paths by cutting them into horizontal bands,
+/// every band is split at the edges crossing it and the spans inside the path become trapezoids
+fn fill(sub_paths: &[SubPath], fill_rule: FillRule) -> ShapeGeometry {
+    let mut edges = sub_paths
+        .iter()
+        .flat_map(|sub_path| {
+            let points = &sub_path.points;
+            (0..points.len()).filter_map(|i| {
+                let from = points[i];
+                let to = points[(i + 1) % points.len()];
+                match from.y.total_cmp(&to.y) {
+                    std::cmp::Ordering::Less => Some(Edge {
+                        bottom: from,
+                        top: to,
+                        winding: 1,
+                    }),
+                    std::cmp::Ordering::Greater => Some(Edge {
+                        bottom: to,
+                        top: from,
+                        winding: -1,
+                    }),
+                    // Horizontal edges never cross a band
+                    std::cmp::Ordering::Equal => None,
+                }
+            })
+        })
+        .collect::<Vec<Edge>>();
+    edges.sort_by(|a, b| a.bottom.y.total_cmp(&b.bottom.y));
+
+    // Bands start and end at every point and crossing, so edges keep their order within a band
+    let mut heights = edges
+        .iter()
+        .flat_map(|edge| [edge.bottom.y, edge.top.y])
+        .collect::<Vec<f32>>();
+    for (i, edge) in edges.iter().enumerate() {
+        for other in edges[i + 1..]
+            .iter()
+            .take_while(|other| other.bottom.y < edge.top.y)
+        {
+            heights.extend(edge.crossing(other));
+        }
+    }
+    heights.sort_by(|a, b| a.total_cmp(b));
+    heights.dedup();
+
+    let mut points = Vec::new();
+    let mut indices = Vec::new();
+    let mut active: Vec<Edge> = Vec::new();
+    let mut next_edge = 0;
+    for band in heights.windows(2) {
+        let (bottom, top) = (band[0], band[1]);
+        active.retain(|edge| edge.top.y > bottom);
+        while next_edge < edges.len() && edges[next_edge].bottom.y <= bottom {
+            active.push(edges[next_edge]);
+            next_edge += 1;
+        }
+
+        let middle = (bottom + top) / 2.;
+        let mut crossings = active
+            .iter()
+            .map(|edge| (edge.x_at(middle), edge))
+            .collect::<Vec<_>>();
+        crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut winding = 0;
+        for pair in crossings.windows(2) {
+            let (left, right) = (pair[0].1, pair[1].1);
+            winding += left.winding;
+            let inside = match fill_rule {
+                FillRule::NonZero => winding != 0,
+                FillRule::EvenOdd => winding % 2 != 0,
+            };
+            if !inside {
+                continue;
+            }
+
+            let offset = points.len() as u32;
+            points.extend([
+                Vec2::new(left.x_at(bottom), bottom),
+                Vec2::new(right.x_at(bottom), bottom),
+                Vec2::new(right.x_at(top), top),
+                Vec2::new(left.x_at(top), top),
+            ]);
+            indices.extend([0, 1, 2, 0, 2, 3].map(|index| index + offset));
+        }
+    }
+
+    ShapeGeometry::from_points(points, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled_area(path: &Path, fill_rule: FillRule) -> f32 {
+        let geometry = fill(
+            &path.flatten(CURVE_TOLERANCE, &Transform::default()),
+            fill_rule,
+        );
+        geometry
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| geometry.points[triangle[i] as usize]);
+                (b - a).perp_dot(c - a).abs() / 2.
+            })
+            .sum()
+    }
+
+    fn square(path: Path, min: f32, max: f32) -> Path {
+        path.move_to(Vec2::splat(min))
+            .line_to(Vec2::new(max, min))
+            .line_to(Vec2::splat(max))
+            .line_to(Vec2::new(min, max))
+            .close()
+    }
+
+    #[test]
+    fn fill_rules() {
+        let path = square(square(Path::new(Vec4::ONE), 0., 10.), 2., 8.);
+
+        assert_eq!(filled_area(&path, FillRule::NonZero), 100.);
+        assert_eq!(filled_area(&path, FillRule::EvenOdd), 100. - 36.);
+    }
+
+    #[test]
+    fn fills_self_intersecting_path() {
+        // A bow tie, the two triangles meet where the edges cross
+        let path = Path::new(Vec4::ONE)
+            .move_to(Vec2::new(0., 0.))
+            .line_to(Vec2::new(4., 4.))
+            .line_to(Vec2::new(4., 0.))
+            .line_to(Vec2::new(0., 4.))
+            .close();
+
+        assert!((filled_area(&path, FillRule::NonZero) - 8.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn flattens_curves_to_their_end_points() {
+        let path = Path::new(Vec4::ONE)
+            .move_to(Vec2::ZERO)
+            .quad_to(Vec2::new(50., 100.), Vec2::new(100., 0.))
+            .cubic_to(Vec2::new(100., -50.), Vec2::new(0., -50.), Vec2::ZERO);
+        let sub_paths = path.flatten(CURVE_TOLERANCE, &Transform::default());
+
+        assert_eq!(sub_paths.len(), 1);
+        assert!(sub_paths[0].points.len() > 4);
+        assert!(sub_paths[0].points.contains(&Vec2::new(100., 0.)));
+        assert_eq!(*sub_paths[0].points.last().unwrap(), Vec2::ZERO);
+    }
+}
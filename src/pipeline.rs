@@ -1,34 +1,112 @@
-use std::fmt::Debug;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+};
 
 use wgpu::{
-    BindGroupLayout, BindingType, BlendState, FragmentState, FrontFace, PolygonMode,
-    PrimitiveState, RenderPipeline, RenderPipelineDescriptor, ShaderStages, TextureFormat,
-    VertexBufferLayout, VertexState, VertexStepMode,
+    BindGroupLayout, FragmentState, FrontFace, PolygonMode, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, TextureFormat, VertexBufferLayout, VertexState, VertexStepMode,
 };
 
 use crate::{
-    bind_groups::BindGroupLayoutBuilder, material::Material, mesh::get_attribute_layout,
-    RenderBuddy,
+    arena::Handle, errors::RenderBuddyError, material::Material, mesh::get_attribute_layout,
+    stencil::StencilMode, RenderBuddy,
 };
 
+/// Describes the render target a [`RenderPipeline`] is built for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct PipelineKey {
+    pub(crate) format: TextureFormat,
+    pub(crate) sample_count: u32,
+    /// Format of the pass's depth stencil attachment, if it has one
+    pub(crate) depth_format: Option<TextureFormat>,
+    pub(crate) stencil_mode: StencilMode,
+}
+
 pub struct Pipeline {
-    pub(crate) render_pipeline: RenderPipeline,
+    pub(crate) render_pipelines: HashMap<PipelineKey, RenderPipeline>,
     pub(crate) material: Box<dyn Material>,
 }
 
+impl Pipeline {
+    pub(crate) fn render_pipeline(&self, key: PipelineKey) -> &RenderPipeline {
+        self.render_pipelines
+            .get(&key)
+            .expect("Material is missing a pipeline for the render target")
+    }
+}
+
 impl Debug for Pipeline {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Pipeline")
-            .field("render_pipeline", &self.render_pipeline)
+            .field("render_pipelines", &self.render_pipelines)
             .field("material", &self.material)
             .finish()
     }
 }
 
 impl RenderBuddy {
-    pub(crate) fn create_pipeline_from_material(
+    pub(crate) fn surface_pipeline_key(&self) -> PipelineKey {
+        PipelineKey {
+            format: self.surface_config.format,
+            sample_count: self.sample_count,
+            depth_format: None,
+            stencil_mode: StencilMode::Disabled,
+        }
+    }
+
+    /// Stores the material, building its pipeline for the surface up front
+    /// so that shader errors are reported when the material is added
+    pub(crate) fn insert_material(
+        &mut self,
+        material: Box<dyn Material>,
+    ) -> Result<Handle<Pipeline>, RenderBuddyError> {
+        let key = self.surface_pipeline_key();
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let render_pipeline = self.create_pipeline_from_material(material.as_ref(), key);
+        if let Some(Some(error)) = poll_ready(self.device.pop_error_scope()) {
+            return Err(RenderBuddyError::Shader(error.to_string()));
+        }
+
+        Ok(self.materials.insert(Pipeline {
+            render_pipelines: HashMap::from([(key, render_pipeline)]),
+            material,
+        }))
+    }
+
+    /// Builds any pipelines missing for the given render target
+    /// Materials are only built for the surface when pushed, other targets are built lazily
+    pub(crate) fn prepare_pipelines(
         &mut self,
-        material: &impl Material,
+        pipelines: impl Iterator<Item = (Handle<Pipeline>, PipelineKey)>,
+    ) {
+        for (material_handle, key) in pipelines {
+            let pipeline = self
+                .materials
+                .get(material_handle)
+                .expect("Mesh was given invalid pipeline id");
+            if pipeline.render_pipelines.contains_key(&key) {
+                continue;
+            }
+
+            let render_pipeline =
+                self.create_pipeline_from_material(pipeline.material.as_ref(), key);
+            self.materials
+                .get_mut(material_handle)
+                .unwrap()
+                .render_pipelines
+                .insert(key, render_pipeline);
+        }
+    }
+
+    pub(crate) fn create_pipeline_from_material(
+        &self,
+        material: &dyn Material,
+        key: PipelineKey,
     ) -> RenderPipeline {
         let shader = self.device.create_shader_module(material.shader());
 
@@ -37,7 +115,7 @@ impl RenderBuddy {
         let (vertex_attribute, offset) = get_attribute_layout(vertex_attributes.iter());
 
         let vertex_buffer_layout = VertexBufferLayout {
-            array_stride: offset as u64,
+            array_stride: offset,
             step_mode: VertexStepMode::Vertex,
             attributes: vertex_attribute.as_slice(),
         };
@@ -45,35 +123,18 @@ impl RenderBuddy {
         let bind_group_layouts: Vec<BindGroupLayout> =
             material.get_bind_group_layouts(&self.device);
 
-        let texture_bind_group_layout = BindGroupLayoutBuilder::new()
-            .append(
-                ShaderStages::FRAGMENT,
-                BindingType::Texture {
-                    multisampled: false,
-                    view_dimension: wgpu::TextureViewDimension::D2,
-                    sample_type: wgpu::TextureSampleType::Float {
-                        filterable: material.filterable_texture(),
-                    },
-                },
-                None,
-            )
-            .append(
-                ShaderStages::FRAGMENT,
-                BindingType::Sampler(if material.filterable_texture() {
-                    wgpu::SamplerBindingType::Filtering
-                } else {
-                    wgpu::SamplerBindingType::NonFiltering
-                }),
-                None,
-            )
-            .build(&self.device, None);
+        let texture_bind_group_layout = if material.filterable_texture() {
+            &self.texture_bind_group_layouts.filterable
+        } else {
+            &self.texture_bind_group_layouts.non_filterable
+        };
 
         let mut predefined_bind_group_layouts = if material.has_texture() {
-            vec![&self.camera_bind_group_layout, &texture_bind_group_layout]
+            vec![&self.camera_bind_group_layout, texture_bind_group_layout]
         } else {
             vec![&self.camera_bind_group_layout]
         };
-        predefined_bind_group_layouts.append(&mut bind_group_layouts.iter().map(|x| &*x).collect());
+        predefined_bind_group_layouts.append(&mut bind_group_layouts.iter().collect());
 
         let render_pipeline_layout =
             self.device
@@ -84,12 +145,13 @@ impl RenderBuddy {
                 });
 
         let binding: [Option<wgpu::ColorTargetState>; 1] = [Some(wgpu::ColorTargetState {
-            format: self.surface_config.format,
-            blend: Some(wgpu::BlendState {
-                color: wgpu::BlendComponent::REPLACE,
-                alpha: wgpu::BlendComponent::REPLACE,
-            }),
-            write_mask: wgpu::ColorWrites::ALL,
+            format: key.format,
+            blend: material.blend_state(),
+            write_mask: if key.stencil_mode == StencilMode::Mask {
+                wgpu::ColorWrites::empty()
+            } else {
+                wgpu::ColorWrites::ALL
+            },
         })];
 
         let descriptor = RenderPipelineDescriptor {
@@ -113,23 +175,31 @@ impl RenderBuddy {
                 topology: material.topology(),
                 strip_index_format: None,
             },
-            depth_stencil: if material.use_depth_stencil() {
-                Some(wgpu::DepthStencilState {
-                    format: TextureFormat::Depth32Float,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState {
-                        constant: 2, // Corresponds to bilinear filtering
-                        slope_scale: 2.0,
-                        clamp: 0.0,
+            // Pipelines always match the pass's attachment, materials only pick whether depth is tested
+            depth_stencil: key.depth_format.map(|format| {
+                let use_depth = material.use_depth_stencil();
+                wgpu::DepthStencilState {
+                    format,
+                    depth_write_enabled: use_depth,
+                    depth_compare: if use_depth {
+                        wgpu::CompareFunction::Less
+                    } else {
+                        wgpu::CompareFunction::Always
                     },
-                })
-            } else {
-                None
-            },
+                    stencil: key.stencil_mode.stencil_state(),
+                    bias: if use_depth {
+                        wgpu::DepthBiasState {
+                            constant: 2, // Corresponds to bilinear filtering
+                            slope_scale: 2.0,
+                            clamp: 0.0,
+                        }
+                    } else {
+                        wgpu::DepthBiasState::default()
+                    },
+                }
+            }),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: key.sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -140,3 +210,21 @@ impl RenderBuddy {
         self.device.create_render_pipeline(&descriptor)
     }
 }
+
+/// Polls a future once, native wgpu resolves error scopes immediately
+fn poll_ready<F: Future>(future: F) -> Option<F::Output> {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(std::ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    // Never woken, the future is only polled once
+    let waker = unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) };
+
+    let mut future = pin!(future);
+    match future.as_mut().poll(&mut Context::from_waker(&waker)) {
+        Poll::Ready(output) => Some(output),
+        Poll::Pending => None,
+    }
+}
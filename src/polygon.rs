@@ -0,0 +1,341 @@
+use glam::{Vec2, Vec4};
+
+use crate::{
+    arena::Handle,
+    gradient::Gradient,
+    mesh::{Mesh, MeshCreator},
+    pipeline::Pipeline,
+    shape::ShapeGeometry,
+    sprite::Anchor,
+    texture::Texture,
+    transform::Transform,
+    RenderBuddy,
+};
+
+/// A filled polygon, concave outlines and holes included
+/// Points are in the local space of the transform it's pushed with, self intersecting outlines aren't supported
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    pub points: Vec<Vec2>,
+    /// Outlines of areas cut out of the polygon, they have to be inside it and not overlap each other
+    pub holes: Vec<Vec<Vec2>>,
+    pub color: Vec4,
+    /// Used instead of the color when set
+    pub gradient: Option<Gradient>,
+    /// Mapped over the bounding box of the polygon, the polygon is drawn in a flat color when `None`
+    pub texture: Option<Handle<Texture>>,
+    pub material: Option<Handle<Pipeline>>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<Vec2>, color: Vec4) -> Self {
+        Self {
+            points,
+            holes: Vec::new(),
+            color,
+            gradient: None,
+            texture: None,
+            material: None,
+        }
+    }
+
+    pub fn with_hole(mut self, hole: Vec<Vec2>) -> Self {
+        self.holes.push(hole);
+        self
+    }
+
+    pub fn with_texture(mut self, texture: Handle<Texture>) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl MeshCreator for Polygon {
+    fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        let mut mesh = triangulate(&self.points, &self.holes).into_mesh(
+            self.color,
+            self.gradient.as_ref(),
+            Anchor::Center,
+            self.material,
+            transform,
+            rb,
+        );
+        if let Some(texture) = self.texture {
+            mesh.texture_handle = Some(texture);
+        }
+
+        mesh
+    }
+}
+
+/// Triangulates a polygon with ear clipping, holes are first joined to the outline through a bridge edge
+/// Works with outlines in either winding order
+pub(crate) fn triangulate(outline: &[Vec2], holes: &[Vec<Vec2>]) -> ShapeGeometry {
+    let mut outline = clean_ring(outline);
+    if outline.len() < 3 {
+        return ShapeGeometry::default();
+    }
+    if signed_area(&outline) < 0. {
+        outline.reverse();
+    }
+
+    let mut holes = holes
+        .iter()
+        .map(|hole| clean_ring(hole))
+        .filter(|hole| hole.len() >= 3)
+        .map(|mut hole| {
+            // Holes wind the opposite way so the bridged outline stays a single counter clockwise loop
+            if signed_area(&hole) > 0. {
+                hole.reverse();
+            }
+            hole
+        })
+        .collect::<Vec<_>>();
+
+    // Bridging the rightmost holes first keeps bridges from crossing holes that aren't joined yet
+    holes.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+    for hole in holes {
+        bridge_hole(&mut outline, &hole);
+    }
+
+    let indices = ear_clip(&outline);
+    ShapeGeometry::from_points(outline, indices)
+}
+
+/// Removes repeated points, including the last point repeating the first
+fn clean_ring(points: &[Vec2]) -> Vec<Vec2> {
+    let mut ring = points.to_vec();
+    ring.dedup();
+    while ring.len() > 1 && ring.first() == ring.last() {
+        ring.pop();
+    }
+
+    ring
+}
+
+/// Positive for counter clockwise rings
+fn signed_area(ring: &[Vec2]) -> f32 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum::<f32>()
+        / 2.
+}
+
+fn max_x(ring: &[Vec2]) -> f32 {
+    ring.iter().map(|point| point.x).fold(f32::MIN, f32::max)
+}
+
+/// Joins the hole to the outline with a zero width cut from its rightmost point to a point of the outline it can see
+fn bridge_hole(outline: &mut Vec<Vec2>, hole: &[Vec2]) {
+    let (hole_index, hole_point) = hole
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.x.total_cmp(&b.x))
+        .unwrap();
+
+    // Cast a ray to the right and find the closest edge it hits
+    let mut closest_hit: Option<(f32, usize)> = None;
+    for i in 0..outline.len() {
+        let a = outline[i];
+        let b = outline[(i + 1) % outline.len()];
+        if a.y == b.y || a.y.min(b.y) > hole_point.y || a.y.max(b.y) < hole_point.y {
+            continue;
+        }
+
+        let x = a.x + (hole_point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+        if x >= hole_point.x && closest_hit.is_none_or(|(closest, _)| x < closest) {
+            // The end of the edge furthest right is a candidate for the bridge
+            let candidate = if a.x > b.x {
+                i
+            } else {
+                (i + 1) % outline.len()
+            };
+            closest_hit = Some((x, candidate));
+        }
+    }
+
+    let bridge_index = match closest_hit {
+        Some((x, candidate)) => {
+            let hit = Vec2::new(x, hole_point.y);
+            let candidate_point = outline[candidate];
+
+            // Points of the outline inside the triangle between the hole, the hit and the candidate can block the bridge,
+            // the one closest in angle to the ray is always visible
+            let mut best = candidate;
+            let mut best_tangent = f32::MAX;
+            for (i, point) in outline.iter().enumerate() {
+                if *point == candidate_point
+                    || point.x < hole_point.x
+                    || !point_in_triangle(*point, hole_point, hit, candidate_point)
+                {
+                    continue;
+                }
+
+                let tangent = (point.y - hole_point.y).abs() / (point.x - hole_point.x);
+                if tangent < best_tangent
+                    || (tangent == best_tangent
+                        && point.distance_squared(hole_point)
+                            < outline[best].distance_squared(hole_point))
+                {
+                    best = i;
+                    best_tangent = tangent;
+                }
+            }
+            best
+        }
+        // Only happens for holes outside the outline
+        None => outline
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.distance_squared(hole_point)
+                    .total_cmp(&b.distance_squared(hole_point))
+            })
+            .map(|(i, _)| i)
+            .unwrap(),
+    };
+
+    let bridge_point = outline[bridge_index];
+    let mut bridged = Vec::with_capacity(outline.len() + hole.len() + 2);
+    bridged.extend_from_slice(&outline[..=bridge_index]);
+    bridged.extend_from_slice(&hole[hole_index..]);
+    bridged.extend_from_slice(&hole[..=hole_index]);
+    bridged.push(bridge_point);
+    bridged.extend_from_slice(&outline[bridge_index + 1..]);
+
+    *outline = bridged;
+}
+
+/// Triangulates a counter clockwise ring, returning indices into it
+fn ear_clip(ring: &[Vec2]) -> Vec<u32> {
+    let mut remaining = (0..ring.len()).collect::<Vec<usize>>();
+    let mut indices = Vec::with_capacity(ring.len().saturating_sub(2) * 3);
+
+    let mut i = 0;
+    let mut attempts = 0;
+    while remaining.len() >= 3 {
+        let len = remaining.len();
+        i %= len;
+        let previous = remaining[(i + len - 1) % len];
+        let current = remaining[i];
+        let next = remaining[(i + 1) % len];
+        let (a, b, c) = (ring[previous], ring[current], ring[next]);
+
+        let cross = (b - a).perp_dot(c - b);
+        if cross == 0. {
+            // Removing a point in a straight line or a spike doesn't change the area
+            remaining.remove(i);
+            attempts = 0;
+            continue;
+        }
+
+        let is_ear = cross > 0.
+            && !remaining.iter().any(|&other| {
+                let point = ring[other];
+                point != a && point != b && point != c && point_in_triangle(point, a, b, c)
+            });
+
+        // Without any ear left the outline intersects itself, clip anyway so it still terminates
+        if is_ear || attempts >= len {
+            indices.extend([previous as u32, current as u32, next as u32]);
+            remaining.remove(i);
+            attempts = 0;
+        } else {
+            i += 1;
+            attempts += 1;
+        }
+    }
+
+    indices
+}
+
+/// Includes points on the edges of the triangle, which has to be counter clockwise
+fn point_in_triangle(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    (b - a).perp_dot(point - a) >= 0.
+        && (c - b).perp_dot(point - b) >= 0.
+        && (a - c).perp_dot(point - c) >= 0.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangulated_area(geometry: &ShapeGeometry) -> f32 {
+        geometry
+            .indices
+            .chunks(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| geometry.points[triangle[i] as usize]);
+                (b - a).perp_dot(c - a) / 2.
+            })
+            .sum()
+    }
+
+    #[test]
+    fn triangulates_square() {
+        let square = [
+            Vec2::new(0., 0.),
+            Vec2::new(2., 0.),
+            Vec2::new(2., 2.),
+            Vec2::new(0., 2.),
+        ];
+        let geometry = triangulate(&square, &[]);
+
+        assert_eq!(geometry.indices.len(), 6);
+        assert_eq!(triangulated_area(&geometry), 4.);
+    }
+
+    #[test]
+    fn triangulates_clockwise_concave_polygon() {
+        let l_shape = [
+            Vec2::new(0., 0.),
+            Vec2::new(0., 3.),
+            Vec2::new(1., 3.),
+            Vec2::new(1., 1.),
+            Vec2::new(3., 1.),
+            Vec2::new(3., 0.),
+        ];
+        let geometry = triangulate(&l_shape, &[]);
+
+        assert_eq!(geometry.indices.len(), 12);
+        assert_eq!(triangulated_area(&geometry), 5.);
+    }
+
+    #[test]
+    fn triangulates_holes() {
+        let square = [
+            Vec2::new(0., 0.),
+            Vec2::new(10., 0.),
+            Vec2::new(10., 10.),
+            Vec2::new(0., 10.),
+        ];
+        let holes = [
+            vec![
+                Vec2::new(2., 2.),
+                Vec2::new(4., 2.),
+                Vec2::new(4., 4.),
+                Vec2::new(2., 4.),
+            ],
+            vec![Vec2::new(6., 6.), Vec2::new(8., 6.), Vec2::new(7., 8.)],
+        ];
+        let geometry = triangulate(&square, &holes);
+
+        assert_eq!(triangulated_area(&geometry), 100. - 4. - 2.);
+        assert!(geometry.indices.chunks(3).all(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| geometry.points[triangle[i] as usize]);
+            (b - a).perp_dot(c - a) >= 0.
+        }));
+    }
+}
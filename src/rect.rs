@@ -4,16 +4,21 @@ use glam::{Vec2, Vec4};
 
 use crate::{
     arena::{ArenaId, Handle},
+    gradient::Gradient,
     mesh::{
         AttributeValue, Mesh, MeshAttribute, MeshCreator, Vertex, QUAD_INDICES, QUAD_UVS,
         QUAD_VERTEX_POSITIONS,
     },
     pipeline::Pipeline,
+    shape::ShapeGeometry,
     sprite::Anchor,
     transform::Transform,
     RenderBuddy,
 };
 
+/// How far in pixels the quad of a rounded rect extends past its edges to fit the anti aliasing
+const ROUNDED_RECT_PADDING: f32 = 1.;
+
 #[derive(Default, Clone, Copy, Debug)]
 pub struct Rect {
     /// The minimum corner point of the rect.
@@ -21,6 +26,13 @@ pub struct Rect {
     /// The maximum corner point of the rect.
     pub max: Vec2,
     pub color: Vec4,
+    /// Radius of each corner, in order top left, top right, bottom right, bottom left
+    pub corner_radii: [f32; 4],
+    /// Width of the border drawn inside the edge of the rect
+    pub border_width: f32,
+    pub border_color: Vec4,
+    /// Used instead of the color when set, the border keeps its own color
+    pub gradient: Option<Gradient>,
     pub anchor: Anchor,
     pub material: Option<Handle<Pipeline>>,
 }
@@ -36,7 +48,16 @@ impl Rect {
             max: size,
             color,
             anchor: Anchor::Center,
-            material: None,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a rect spanning the two corners, e.g. for a clip rect
+    pub fn from_corners(min: Vec2, max: Vec2) -> Self {
+        Self {
+            min,
+            max,
+            ..Default::default()
         }
     }
 
@@ -44,6 +65,80 @@ impl Rect {
         self.anchor = anchor;
         self
     }
+
+    /// Rounds every corner with the same radius
+    pub fn with_corner_radius(mut self, radius: f32) -> Self {
+        self.corner_radii = [radius; 4];
+        self
+    }
+
+    /// Rounds each corner separately, in order top left, top right, bottom right, bottom left
+    pub fn with_corner_radii(mut self, corner_radii: [f32; 4]) -> Self {
+        self.corner_radii = corner_radii;
+        self
+    }
+
+    pub fn with_border(mut self, width: f32, color: Vec4) -> Self {
+        self.border_width = width;
+        self.border_color = color;
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    /// Rounded and bordered rects are drawn with the rounded rect material
+    fn is_rounded(&self) -> bool {
+        self.border_width > 0. || self.corner_radii.iter().any(|radius| *radius > 0.)
+    }
+
+    /// The area covered by both rects, empty if they don't overlap
+    pub fn intersect(&self, other: Rect) -> Rect {
+        let min = self.min.max(other.min);
+        let max = self.max.min(other.max).max(min);
+
+        Rect::from_corners(min, max)
+    }
+}
+
+impl RenderBuddy {
+    /// Clips every mesh pushed after this to the rect until the matching [`RenderBuddy::pop_clip`]
+    /// The rect is in pixels of the render target, with the origin in the top left corner
+    /// Nested clips are intersected with the clip they are pushed inside of
+    pub fn push_clip(&mut self, clip: Rect) {
+        let clip = match self.clip_stack.last() {
+            Some(current_clip) => current_clip.intersect(clip),
+            None => Rect::from_corners(clip.min, clip.max),
+        };
+
+        self.clip_stack.push(clip);
+    }
+
+    /// Removes the last clip pushed with [`RenderBuddy::push_clip`], returning the clip that was applied
+    pub fn pop_clip(&mut self) -> Option<Rect> {
+        self.clip_stack.pop()
+    }
+
+    /// Converts a clip into a scissor rect inside the target, `None` if nothing is left to draw
+    pub(crate) fn clip_to_scissor(clip: Rect, target_size: (u32, u32)) -> Option<[u32; 4]> {
+        let target_size = Vec2::new(target_size.0 as f32, target_size.1 as f32);
+        let min = clip.min.floor().clamp(Vec2::ZERO, target_size);
+        let max = clip.max.ceil().clamp(Vec2::ZERO, target_size);
+        let size = max - min;
+
+        if size.x <= 0. || size.y <= 0. {
+            return None;
+        }
+
+        Some([min.x as u32, min.y as u32, size.x as u32, size.y as u32])
+    }
 }
 
 impl Add<Vec2> for Rect {
@@ -52,15 +147,34 @@ impl Add<Vec2> for Rect {
         Self {
             min: self.min + other,
             max: self.max + other,
-            color: self.color,
-            anchor: self.anchor,
-            material: self.material,
+            ..self
         }
     }
 }
 
 impl MeshCreator for Rect {
     fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        if self.is_rounded() {
+            return self.build_rounded(transform, rb);
+        }
+        if let Some(gradient) = &self.gradient {
+            let size = self.size();
+            return ShapeGeometry::from_points(
+                QUAD_VERTEX_POSITIONS
+                    .map(|quad_pos| quad_pos * size)
+                    .to_vec(),
+                QUAD_INDICES.to_vec(),
+            )
+            .into_mesh(
+                self.color,
+                Some(gradient),
+                self.anchor,
+                self.material,
+                transform,
+                rb,
+            );
+        }
+
         let quad_size = self.size();
 
         let positions: [[f32; 3]; 4] = QUAD_VERTEX_POSITIONS.map(|quad_pos| {
@@ -95,3 +209,119 @@ impl MeshCreator for Rect {
         )
     }
 }
+
+impl Rect {
+    /// A single quad, slightly larger than the rect so the anti aliased edge isn't cut off,
+    /// the shape is drawn by the rounded rect material from the custom attributes
+    fn build_rounded(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        let size = self.size().abs();
+        let half_size = size / 2.;
+        let max_radius = half_size.min_element();
+        let corner_radii = self.corner_radii.map(|radius| radius.clamp(0., max_radius));
+
+        let scale = transform.scale.x.abs().min(transform.scale.y.abs());
+        let padding = ROUNDED_RECT_PADDING / scale.max(f32::EPSILON);
+
+        let mut geometry = ShapeGeometry {
+            points: QUAD_VERTEX_POSITIONS
+                .map(|quad_pos| quad_pos * size + quad_pos.signum() * padding)
+                .to_vec(),
+            indices: QUAD_INDICES.to_vec(),
+            min: -half_size,
+            max: half_size,
+            colors: Vec::new(),
+        };
+        if let Some(gradient) = &self.gradient {
+            gradient.apply(&mut geometry, &transform);
+        }
+
+        let uv_size = size.max(Vec2::splat(f32::EPSILON));
+        let vertices = geometry
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, local)| {
+                let position = transform
+                    .transform_point((*local - self.anchor.as_vec() * size).extend(0.))
+                    .into();
+                let uv = Vec2::new(local.x + half_size.x, half_size.y - local.y) / uv_size;
+                let color = geometry.colors.get(i).copied().unwrap_or(self.color);
+
+                Vertex(BTreeMap::from([
+                    (MeshAttribute::Position, AttributeValue::Position(position)),
+                    (MeshAttribute::UV, AttributeValue::UV(uv.into())),
+                    (MeshAttribute::Color, AttributeValue::Color(color.into())),
+                    (
+                        MeshAttribute::Custom(0),
+                        AttributeValue::Custom(0, [local.x, local.y, half_size.x, half_size.y]),
+                    ),
+                    (
+                        MeshAttribute::Custom(1),
+                        AttributeValue::Custom(1, corner_radii),
+                    ),
+                    (
+                        MeshAttribute::Custom(2),
+                        AttributeValue::Custom(2, self.border_color.into()),
+                    ),
+                    (
+                        MeshAttribute::Custom(3),
+                        AttributeValue::Custom(3, [self.border_width.max(0.), 0., 0., 0.]),
+                    ),
+                ]))
+            })
+            .collect();
+
+        Mesh::new(
+            Some(Handle::new(ArenaId::first())),
+            self.material.unwrap_or(rb.material_map.rounded_rect),
+            vertices,
+            geometry.indices,
+            transform.position.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_clips_intersect() {
+        let outer = Rect::from_corners(Vec2::new(10., 10.), Vec2::new(100., 80.));
+        let inner = Rect::from_corners(Vec2::new(50., 0.), Vec2::new(200., 60.));
+        let innermost = Rect::from_corners(Vec2::new(0., 20.), Vec2::new(70., 200.));
+
+        let clip = outer.intersect(inner).intersect(innermost);
+        assert_eq!(clip.min, Vec2::new(50., 20.));
+        assert_eq!(clip.max, Vec2::new(70., 60.));
+    }
+
+    #[test]
+    fn disjoint_clips_are_empty() {
+        let left = Rect::from_corners(Vec2::ZERO, Vec2::new(10., 10.));
+        let right = Rect::from_corners(Vec2::new(20., 5.), Vec2::new(30., 30.));
+
+        let clip = left.intersect(right);
+        assert_eq!(clip.size().x, 0.);
+        assert_eq!(RenderBuddy::clip_to_scissor(clip, (100, 100)), None);
+        // Clipping an empty clip further keeps it empty
+        assert_eq!(clip.intersect(left).size().x, 0.);
+    }
+
+    #[test]
+    fn scissor_is_clamped_to_the_target() {
+        let clip = Rect::from_corners(Vec2::new(-20., 10.5), Vec2::new(50.2, 300.));
+
+        assert_eq!(
+            RenderBuddy::clip_to_scissor(clip, (40, 100)),
+            Some([0, 10, 40, 90])
+        );
+    }
+
+    #[test]
+    fn clips_outside_the_target_are_skipped() {
+        let clip = Rect::from_corners(Vec2::new(120., 10.), Vec2::new(150., 50.));
+
+        assert_eq!(RenderBuddy::clip_to_scissor(clip, (100, 100)), None);
+    }
+}
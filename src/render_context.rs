@@ -1,7 +1,7 @@
 use wgpu::{CommandEncoder, SurfaceTexture, TextureView};
 
 pub struct RenderContext {
-    pub(crate) output: SurfaceTexture,
+    pub(crate) output: Option<SurfaceTexture>,
     pub(crate) view: TextureView,
     pub(crate) command_encoder: CommandEncoder,
 }
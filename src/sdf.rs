@@ -0,0 +1,178 @@
+/// Font size distance fields are generated at, they're scaled to the size of the text when drawn
+pub(crate) const SDF_FONT_SIZE: f32 = 64.;
+/// Texels between the edge of a glyph and the points where its distance field reaches 0 and 1,
+/// outlines and shadows can't reach further than this
+pub(crate) const SDF_SPREAD: usize = 8;
+/// Glyphs are rasterized this many times bigger than their distance field so its edges are precise
+pub(crate) const SDF_SUPERSAMPLING: usize = 4;
+
+/// Stands in for an infinite distance, infinity itself would turn the transform into NaN
+const FAR: f32 = 1e20;
+
+/// Distance field of a glyph mask rasterized [`SDF_SUPERSAMPLING`] times bigger than the field
+/// `mask_origin` is where the top left corner of the mask is in the supersampled field
+/// Values are 0.5 on the edge of the glyph, going up to 1 inside and down to 0 outside over [`SDF_SPREAD`] texels
+pub(crate) fn distance_field(
+    mask: &[u8],
+    mask_size: (usize, usize),
+    mask_origin: (usize, usize),
+    field_size: (usize, usize),
+) -> Vec<u8> {
+    let (width, height) = (
+        field_size.0 * SDF_SUPERSAMPLING,
+        field_size.1 * SDF_SUPERSAMPLING,
+    );
+    let mut inside = vec![false; width * height];
+    for y in 0..mask_size.1 {
+        for x in 0..mask_size.0 {
+            inside[(y + mask_origin.1) * width + x + mask_origin.0] =
+                mask[y * mask_size.0 + x] >= 128;
+        }
+    }
+
+    let to_inside = squared_distances(&inside, width, height, true);
+    let to_outside = squared_distances(&inside, width, height, false);
+
+    // Distances are measured between pixel centers, the edge is half a pixel closer
+    let signed_distances = inside
+        .iter()
+        .enumerate()
+        .map(|(i, inside)| {
+            if *inside {
+                to_outside[i].sqrt() - 0.5
+            } else {
+                0.5 - to_inside[i].sqrt()
+            }
+        })
+        .collect::<Vec<f32>>();
+
+    let block_area = (SDF_SUPERSAMPLING * SDF_SUPERSAMPLING) as f32;
+    let mut field = Vec::with_capacity(field_size.0 * field_size.1);
+    for field_y in 0..field_size.1 {
+        for field_x in 0..field_size.0 {
+            let mut distance = 0.;
+            for y in field_y * SDF_SUPERSAMPLING..(field_y + 1) * SDF_SUPERSAMPLING {
+                for x in field_x * SDF_SUPERSAMPLING..(field_x + 1) * SDF_SUPERSAMPLING {
+                    distance += signed_distances[y * width + x];
+                }
+            }
+
+            let texels = distance / block_area / SDF_SUPERSAMPLING as f32;
+            let value = 0.5 + texels / (2 * SDF_SPREAD) as f32;
+            field.push((value.clamp(0., 1.) * 255.).round() as u8);
+        }
+    }
+
+    field
+}
+
+/// Squared distance from every pixel to the closest pixel that is `inside` or not
+fn squared_distances(inside: &[bool], width: usize, height: usize, target: bool) -> Vec<f32> {
+    let mut distances = inside
+        .iter()
+        .map(|inside| if *inside == target { 0. } else { FAR })
+        .collect::<Vec<f32>>();
+
+    let length = width.max(height);
+    let mut line = vec![0.; length];
+    let mut transformed = vec![0.; length];
+    for x in 0..width {
+        for y in 0..height {
+            line[y] = distances[y * width + x];
+        }
+        transform_line(&line[..height], &mut transformed[..height]);
+        for y in 0..height {
+            distances[y * width + x] = transformed[y];
+        }
+    }
+    for row in distances.chunks_exact_mut(width) {
+        line[..width].copy_from_slice(row);
+        transform_line(&line[..width], row);
+    }
+
+    distances
+}
+
+/// The 1D squared distance transform from Felzenszwalb and Huttenlocher,
+/// the lower envelope of the parabolas rooted at every point
+fn transform_line(values: &[f32], distances: &mut [f32]) {
+    let mut roots = vec![0usize; values.len()];
+    let mut boundaries = vec![0f32; values.len() + 1];
+    let parabola_intersection = |q: usize, p: usize| {
+        let (q_f, p_f) = (q as f32, p as f32);
+        ((values[q] + q_f * q_f) - (values[p] + p_f * p_f)) / (2. * q_f - 2. * p_f)
+    };
+
+    let mut k = 0;
+    boundaries[0] = f32::NEG_INFINITY;
+    boundaries[1] = f32::INFINITY;
+    for q in 1..values.len() {
+        let mut intersection = parabola_intersection(q, roots[k]);
+        while intersection <= boundaries[k] {
+            k -= 1;
+            intersection = parabola_intersection(q, roots[k]);
+        }
+        k += 1;
+        roots[k] = q;
+        boundaries[k] = intersection;
+        boundaries[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+    for (q, distance) in distances.iter_mut().enumerate() {
+        while boundaries[k + 1] < q as f32 {
+            k += 1;
+        }
+        let offset = q as f32 - roots[k] as f32;
+        *distance = offset * offset + values[roots[k]];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms_line() {
+        let values = [FAR, 0., FAR, FAR, FAR, 0.];
+        let mut distances = [0.; 6];
+        transform_line(&values, &mut distances);
+
+        assert_eq!(distances, [1., 0., 1., 4., 1., 0.]);
+    }
+
+    #[test]
+    fn square_field_has_edge_at_half() {
+        // A 16 by 16 texel square in the middle of the field
+        let mask_size = 16 * SDF_SUPERSAMPLING;
+        let mask = vec![255; mask_size * mask_size];
+        let padding = SDF_SPREAD * SDF_SUPERSAMPLING;
+        let field_size = 16 + SDF_SPREAD * 2;
+        let field = distance_field(
+            &mask,
+            (mask_size, mask_size),
+            (padding, padding),
+            (field_size, field_size),
+        );
+
+        let row = &field[field_size * (field_size / 2)..field_size * (field_size / 2 + 1)];
+        // Texels on either side of the edge are half a texel from it
+        let half_texel = 255. / (4 * SDF_SPREAD) as f32;
+        assert!((row[SDF_SPREAD] as f32 - (127.5 + half_texel)).abs() <= 1.);
+        assert!((row[SDF_SPREAD - 1] as f32 - (127.5 - half_texel)).abs() <= 1.);
+        // The first texel is half a texel short of the spread
+        assert!((row[0] as f32 - half_texel).abs() <= 1.);
+        // The middle is 7.5 texels from the closest edge, averaging the supersamples brings it down a little
+        assert!(row[field_size / 2] > 240);
+        assert!(row[..field_size / 2]
+            .windows(2)
+            .all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn empty_mask_is_outside_everywhere() {
+        let field = distance_field(&[], (0, 0), (0, 0), (4, 4));
+
+        assert!(field.iter().all(|value| *value == 0));
+    }
+}
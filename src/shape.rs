@@ -0,0 +1,522 @@
+use std::{
+    collections::BTreeMap,
+    f32::consts::{PI, TAU},
+};
+
+use glam::{Vec2, Vec4};
+
+use crate::{
+    arena::{ArenaId, Handle},
+    gradient::Gradient,
+    mesh::{AttributeValue, Mesh, MeshAttribute, MeshCreator, Vertex},
+    pipeline::Pipeline,
+    sprite::Anchor,
+    transform::Transform,
+    RenderBuddy,
+};
+
+/// Max distance in pixels between a curve and the segments it's drawn with when tessellating automatically
+pub(crate) const CURVE_TOLERANCE: f32 = 0.25;
+const MIN_SEGMENTS: u32 = 8;
+pub(crate) const MAX_SEGMENTS: u32 = 256;
+
+/// Whether a shape is filled or only its outline is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ShapeMode {
+    #[default]
+    Fill,
+    /// Draws the outline with the given thickness, centered on the edge of the shape
+    Stroke(f32),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ellipse {
+    pub radii: Vec2,
+    pub color: Vec4,
+    /// Used instead of the color when set
+    pub gradient: Option<Gradient>,
+    pub mode: ShapeMode,
+    /// Number of segments the outline is made of,
+    /// picked from the radius and the transform's scale when `None`
+    pub segments: Option<u32>,
+    pub anchor: Anchor,
+    pub material: Option<Handle<Pipeline>>,
+}
+
+impl Ellipse {
+    pub fn new(radii: Vec2, color: Vec4) -> Self {
+        Self {
+            radii,
+            color,
+            gradient: None,
+            mode: ShapeMode::Fill,
+            segments: None,
+            anchor: Anchor::Center,
+            material: None,
+        }
+    }
+
+    pub fn with_stroke(mut self, thickness: f32) -> Self {
+        self.mode = ShapeMode::Stroke(thickness);
+        self
+    }
+
+    pub fn with_segments(mut self, segments: u32) -> Self {
+        self.segments = Some(segments);
+        self
+    }
+
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl MeshCreator for Ellipse {
+    fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        let segments = segment_count(self.segments, self.radii.max_element(), &transform);
+
+        ShapeGeometry::arc(self.radii, 0., TAU, segments, self.mode, false).into_mesh(
+            self.color,
+            self.gradient.as_ref(),
+            self.anchor,
+            self.material,
+            transform,
+            rb,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Circle {
+    pub radius: f32,
+    pub color: Vec4,
+    /// Used instead of the color when set
+    pub gradient: Option<Gradient>,
+    pub mode: ShapeMode,
+    /// Number of segments the outline is made of,
+    /// picked from the radius and the transform's scale when `None`
+    pub segments: Option<u32>,
+    pub anchor: Anchor,
+    pub material: Option<Handle<Pipeline>>,
+}
+
+impl Circle {
+    pub fn new(radius: f32, color: Vec4) -> Self {
+        Self {
+            radius,
+            color,
+            gradient: None,
+            mode: ShapeMode::Fill,
+            segments: None,
+            anchor: Anchor::Center,
+            material: None,
+        }
+    }
+
+    pub fn with_stroke(mut self, thickness: f32) -> Self {
+        self.mode = ShapeMode::Stroke(thickness);
+        self
+    }
+
+    pub fn with_segments(mut self, segments: u32) -> Self {
+        self.segments = Some(segments);
+        self
+    }
+
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl MeshCreator for Circle {
+    fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        Ellipse {
+            radii: Vec2::splat(self.radius),
+            color: self.color,
+            gradient: self.gradient,
+            mode: self.mode,
+            segments: self.segments,
+            anchor: self.anchor,
+            material: self.material,
+        }
+        .build(transform, rb)
+    }
+}
+
+/// Part of a circle between two angles, filling it draws a pie slice
+/// Angles are in radians, counter clockwise starting from the right
+#[derive(Debug, Clone, Copy)]
+pub struct Arc {
+    pub radius: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub color: Vec4,
+    /// Used instead of the color when set
+    pub gradient: Option<Gradient>,
+    pub mode: ShapeMode,
+    /// Number of segments a full circle would be made of,
+    /// picked from the radius and the transform's scale when `None`
+    pub segments: Option<u32>,
+    /// Relative to the full circle, so arcs of the same circle line up
+    pub anchor: Anchor,
+    pub material: Option<Handle<Pipeline>>,
+}
+
+impl Arc {
+    pub fn new(radius: f32, start_angle: f32, end_angle: f32, color: Vec4) -> Self {
+        Self {
+            radius,
+            start_angle,
+            end_angle,
+            color,
+            gradient: None,
+            mode: ShapeMode::Fill,
+            segments: None,
+            anchor: Anchor::Center,
+            material: None,
+        }
+    }
+
+    /// A pie slice covering `fraction` of the circle clockwise from the top, e.g. for cooldown timers
+    pub fn pie(radius: f32, fraction: f32, color: Vec4) -> Self {
+        Self::new(radius, PI / 2. - fraction * TAU, PI / 2., color)
+    }
+
+    pub fn with_stroke(mut self, thickness: f32) -> Self {
+        self.mode = ShapeMode::Stroke(thickness);
+        self
+    }
+
+    pub fn with_segments(mut self, segments: u32) -> Self {
+        self.segments = Some(segments);
+        self
+    }
+
+    pub fn with_anchor(mut self, anchor: Anchor) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_material(mut self, material: Handle<Pipeline>) -> Self {
+        self.material = Some(material);
+        self
+    }
+}
+
+impl MeshCreator for Arc {
+    fn build(&self, transform: Transform, rb: &RenderBuddy) -> Mesh {
+        let sweep = (self.end_angle - self.start_angle).clamp(-TAU, TAU);
+        let full_segments = segment_count(self.segments, self.radius, &transform);
+        let segments = ((full_segments as f32 * sweep.abs() / TAU).ceil() as u32).max(1);
+
+        ShapeGeometry::arc(
+            Vec2::splat(self.radius),
+            self.start_angle,
+            sweep,
+            segments,
+            self.mode,
+            sweep.abs() < TAU,
+        )
+        .into_mesh(
+            self.color,
+            self.gradient.as_ref(),
+            self.anchor,
+            self.material,
+            transform,
+            rb,
+        )
+    }
+}
+
+/// Picks how many segments a full circle needs to look smooth at its size on screen
+pub(crate) fn segment_count(segments: Option<u32>, radius: f32, transform: &Transform) -> u32 {
+    segments
+        .unwrap_or_else(|| {
+            let radius = radius.abs() * transform.scale.x.abs().max(transform.scale.y.abs());
+            if radius <= CURVE_TOLERANCE {
+                return MIN_SEGMENTS;
+            }
+
+            let segment_angle = 2. * (1. - CURVE_TOLERANCE / radius).acos();
+            ((TAU / segment_angle).ceil() as u32).clamp(MIN_SEGMENTS, MAX_SEGMENTS)
+        })
+        .max(3)
+}
+
+/// Triangulated 2d shape in local space
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ShapeGeometry {
+    pub(crate) points: Vec<Vec2>,
+    pub(crate) indices: Vec<u32>,
+    /// Bounding box the anchor and uvs are relative to
+    pub(crate) min: Vec2,
+    pub(crate) max: Vec2,
+    /// Color of each point when a gradient was applied, empty otherwise
+    pub(crate) colors: Vec<Vec4>,
+}
+
+impl ShapeGeometry {
+    /// Geometry bounded by its own points
+    pub(crate) fn from_points(points: Vec<Vec2>, indices: Vec<u32>) -> Self {
+        let (min, max) = points.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), point| (min.min(*point), max.max(*point)),
+        );
+        if points.is_empty() {
+            return Self::default();
+        }
+
+        Self {
+            points,
+            indices,
+            min,
+            max,
+            colors: Vec::new(),
+        }
+    }
+
+    /// Appends another geometry, growing the bounds to fit it
+    pub(crate) fn append(&mut self, other: ShapeGeometry) {
+        if other.points.is_empty() {
+            return;
+        }
+        if self.points.is_empty() {
+            *self = other;
+            return;
+        }
+
+        let offset = self.points.len() as u32;
+        self.points.extend(other.points);
+        self.indices
+            .extend(other.indices.iter().map(|index| index + offset));
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// An ellipse or part of one, `open` arcs are filled as a pie and their stroke isn't closed
+    pub(crate) fn arc(
+        radii: Vec2,
+        start_angle: f32,
+        sweep: f32,
+        segments: u32,
+        mode: ShapeMode,
+        open: bool,
+    ) -> Self {
+        let point_count = if open { segments + 1 } else { segments };
+        let outline = (0..point_count).map(|i| {
+            let angle = start_angle + sweep * i as f32 / segments as f32;
+            Vec2::new(angle.cos(), angle.sin())
+        });
+
+        let mut geometry = ShapeGeometry {
+            min: -radii,
+            max: radii,
+            ..Default::default()
+        };
+
+        match mode {
+            ShapeMode::Fill => {
+                geometry.points.push(Vec2::ZERO);
+                geometry
+                    .points
+                    .extend(outline.map(|direction| direction * radii));
+                for i in 1..segments + 1 {
+                    let next = if i == point_count { 1 } else { i + 1 };
+                    geometry.indices.extend([0, i, next]);
+                }
+            }
+            ShapeMode::Stroke(thickness) => {
+                let half_thickness = thickness / 2.;
+                let outer = radii + half_thickness;
+                let inner = (radii - half_thickness).max(Vec2::ZERO);
+                for direction in outline {
+                    geometry.points.push(direction * outer);
+                    geometry.points.push(direction * inner);
+                }
+                for i in 0..segments {
+                    let current = i * 2;
+                    let next = ((i + 1) % point_count) * 2;
+                    geometry.indices.extend([
+                        current,
+                        next,
+                        current + 1,
+                        current + 1,
+                        next,
+                        next + 1,
+                    ]);
+                }
+            }
+        }
+
+        geometry
+    }
+
+    /// Builds the mesh, offsetting the points by the anchor scaled with the size of the bounding box
+    /// and mapping uvs from the bounding box, the gradient replaces the color if there is one
+    pub(crate) fn into_mesh(
+        mut self,
+        color: Vec4,
+        gradient: Option<&Gradient>,
+        anchor: Anchor,
+        material: Option<Handle<Pipeline>>,
+        transform: Transform,
+        rb: &RenderBuddy,
+    ) -> Mesh {
+        if let Some(gradient) = gradient {
+            gradient.apply(&mut self, &transform);
+        }
+
+        let size = self.max - self.min;
+        let offset = anchor.as_vec() * size;
+        let size = size.max(Vec2::splat(f32::EPSILON));
+
+        let vertices = self
+            .points
+            .iter()
+            .enumerate()
+            .map(|(i, point)| {
+                let color = self.colors.get(i).copied().unwrap_or(color);
+                let uv = Vec2::new(
+                    (point.x - self.min.x) / size.x,
+                    (self.max.y - point.y) / size.y,
+                );
+                Vertex(BTreeMap::from([
+                    (
+                        MeshAttribute::Position,
+                        AttributeValue::Position(
+                            transform
+                                .transform_point((*point - offset).extend(0.))
+                                .into(),
+                        ),
+                    ),
+                    (MeshAttribute::UV, AttributeValue::UV(uv.into())),
+                    (MeshAttribute::Color, AttributeValue::Color(color.into())),
+                ]))
+            })
+            .collect();
+
+        Mesh::new(
+            Some(Handle::new(ArenaId::first())),
+            material.unwrap_or(rb.material_map.default),
+            vertices,
+            self.indices,
+            transform.position.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    fn assert_indices_valid(geometry: &ShapeGeometry) {
+        assert_eq!(geometry.indices.len() % 3, 0);
+        assert!(geometry
+            .indices
+            .iter()
+            .all(|index| (*index as usize) < geometry.points.len()));
+    }
+
+    #[test]
+    fn closed_fill_wraps_around() {
+        let geometry = ShapeGeometry::arc(Vec2::ONE, 0., TAU, 8, ShapeMode::Fill, false);
+
+        assert_indices_valid(&geometry);
+        // Center and one point per segment
+        assert_eq!(geometry.points.len(), 9);
+        assert_eq!(geometry.indices.len() / 3, 8);
+        assert_eq!(geometry.indices[geometry.indices.len() - 3..], [0, 8, 1]);
+    }
+
+    #[test]
+    fn open_fill_is_a_pie() {
+        let geometry = ShapeGeometry::arc(Vec2::ONE, 0., PI, 4, ShapeMode::Fill, true);
+
+        assert_indices_valid(&geometry);
+        assert_eq!(geometry.points.len(), 6);
+        assert_eq!(geometry.indices.len() / 3, 4);
+        // The last triangle ends on the last point instead of going back to the first
+        assert_eq!(geometry.indices[geometry.indices.len() - 3..], [0, 4, 5]);
+        assert!((geometry.points[5] - Vec2::new(-1., 0.)).length() < 1e-5);
+    }
+
+    #[test]
+    fn closed_stroke_wraps_around() {
+        let geometry = ShapeGeometry::arc(Vec2::ONE, 0., TAU, 8, ShapeMode::Stroke(0.5), false);
+
+        assert_indices_valid(&geometry);
+        // An outer and inner point per segment, two triangles between each pair
+        assert_eq!(geometry.points.len(), 16);
+        assert_eq!(geometry.indices.len() / 3, 16);
+        assert_eq!(
+            geometry.indices[geometry.indices.len() - 6..],
+            [14, 0, 15, 15, 0, 1]
+        );
+        assert!((geometry.points[0].length() - 1.25).abs() < 1e-5);
+        assert!((geometry.points[1].length() - 0.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn open_stroke_isnt_closed() {
+        let geometry = ShapeGeometry::arc(Vec2::ONE, 0., PI, 4, ShapeMode::Stroke(4.), true);
+
+        assert_indices_valid(&geometry);
+        assert_eq!(geometry.points.len(), 10);
+        assert_eq!(geometry.indices.len() / 3, 8);
+        assert!(!geometry.indices[geometry.indices.len() - 6..].contains(&0));
+        // Strokes thicker than the radius don't turn the inner edge inside out
+        assert_eq!(geometry.points[1], Vec2::ZERO);
+    }
+
+    #[test]
+    fn segment_count_is_clamped() {
+        let transform = Transform::default();
+
+        assert_eq!(segment_count(None, 0.1, &transform), MIN_SEGMENTS);
+        assert_eq!(segment_count(None, 1e6, &transform), MAX_SEGMENTS);
+        // Explicit counts are kept as long as they make a shape
+        assert_eq!(segment_count(Some(1), 10., &transform), 3);
+        assert_eq!(segment_count(Some(1000), 10., &transform), 1000);
+    }
+
+    #[test]
+    fn segment_count_follows_the_scale() {
+        let radius = 20.;
+        let scaled = Transform {
+            scale: Vec3::new(1., -4., 1.),
+            ..Default::default()
+        };
+
+        let segments = segment_count(None, radius, &Transform::default());
+        assert!(segments > MIN_SEGMENTS && segments < MAX_SEGMENTS);
+        assert!(segment_count(None, radius, &scaled) > segments);
+    }
+}
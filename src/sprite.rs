@@ -4,12 +4,14 @@ use glam::Vec2;
 
 use crate::{
     arena::{ArenaId, Handle},
+    gradient::Gradient,
     mesh::{
         AttributeValue, Mesh, MeshAttribute, MeshBuilder, MeshCreator, Vertex, QUAD_INDICES,
         QUAD_UVS, QUAD_VERTEX_POSITIONS,
     },
     pipeline::Pipeline,
     rect::Rect,
+    shape::ShapeGeometry,
     texture::Texture,
     transform::Transform,
     RenderBuddy,
@@ -21,10 +23,20 @@ pub struct Sprite {
     pub material: Option<Handle<Pipeline>>,
     pub anchor: Anchor,
     pub color: [f32; 4],
+    /// Tints the sprite instead of the color when set
+    pub gradient: Option<Gradient>,
     pub texture_rect: Option<Rect>,
     pub custom_size: Option<Vec2>,
     pub flip_x: bool,
     pub flip_y: bool,
+    /// Repeats the texture at its own size across the custom size instead of stretching it
+    /// Needs a texture with a repeating sampler, see [`TextureSamplerType::Repeat`](crate::texture::TextureSamplerType::Repeat),
+    /// unless only a texture rect is repeated, which gets a quad for every repeat instead
+    pub tiled: bool,
+    /// Scales the uvs, values above 1 repeat the texture more often when it has a repeating sampler
+    pub uv_scale: Vec2,
+    /// Moves the uvs, change it over time to scroll the texture
+    pub uv_offset: Vec2,
 }
 
 impl Default for Sprite {
@@ -34,10 +46,14 @@ impl Default for Sprite {
             material: None,
             anchor: Anchor::default(),
             color: [1., 1., 1., 1.],
+            gradient: None,
             texture_rect: None,
             custom_size: None,
             flip_x: false,
             flip_y: false,
+            tiled: false,
+            uv_scale: Vec2::ONE,
+            uv_offset: Vec2::ZERO,
         }
     }
 }
@@ -53,6 +69,34 @@ impl Sprite {
         self.anchor = anchor;
         self
     }
+
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    pub fn with_custom_size(mut self, custom_size: Vec2) -> Self {
+        self.custom_size = Some(custom_size);
+        self
+    }
+
+    /// Repeats the texture across the given size, see [`Sprite::tiled`]
+    /// With a texture rect only the rect is repeated, one quad per repeat
+    pub fn with_tiled(mut self, size: Vec2) -> Self {
+        self.custom_size = Some(size);
+        self.tiled = true;
+        self
+    }
+
+    pub fn with_uv_scale(mut self, uv_scale: Vec2) -> Self {
+        self.uv_scale = uv_scale;
+        self
+    }
+
+    pub fn with_uv_offset(mut self, uv_offset: Vec2) -> Self {
+        self.uv_offset = uv_offset;
+        self
+    }
 }
 
 impl MeshCreator for Sprite {
@@ -73,45 +117,88 @@ impl MeshCreator for Sprite {
 
         let current_image_size = texture.dimensions;
 
-        // By default, the size of the quad is the size of the texture
-        let mut quad_size = current_image_size;
-
-        // If a rect is specified, adjust UVs and the size of the quad
-        if let Some(rect) = self.texture_rect {
-            let rect_size = rect.size();
-            for uv in &mut uvs {
-                *uv = (rect.min + *uv * rect_size) / current_image_size;
-            }
-            quad_size = rect_size;
-        }
+        // By default, the size of the quad is the size of the texture, or of the rect if one is specified
+        let source_size = self
+            .texture_rect
+            .map_or(current_image_size, |rect| rect.size());
 
         // Override the size if a custom one is specified
-        if let Some(custom_size) = self.custom_size {
-            quad_size = custom_size;
+        let quad_size = self.custom_size.unwrap_or(source_size);
+
+        let uv_scale = if self.tiled {
+            self.uv_scale * quad_size / source_size
+        } else {
+            self.uv_scale
+        };
+        for uv in &mut uvs {
+            *uv = *uv * uv_scale + self.uv_offset;
         }
 
-        let positions: [[f32; 3]; 4] = QUAD_VERTEX_POSITIONS.map(|quad_pos| {
-            transform
-                .transform_point(((quad_pos - self.anchor.as_vec()) * quad_size).extend(0.))
-                .into()
-        });
-
-        let vertices = positions
-            .iter()
-            .zip(uvs)
-            .map(|(position, uv)| {
-                Vertex(BTreeMap::from([
-                    (MeshAttribute::Position, AttributeValue::Position(*position)),
-                    (MeshAttribute::UV, AttributeValue::UV(uv.into())),
-                    (MeshAttribute::Color, AttributeValue::Color(self.color)),
-                ]))
-            })
-            .collect();
+        // Samplers repeat the whole texture, so each repeat of a texture rect gets its own quad
+        let (columns, rows) = if self.tiled && self.texture_rect.is_some() {
+            (
+                tile_segments(uvs[0].x, uvs[1].x),
+                tile_segments(uvs[0].y, uvs[3].y),
+            )
+        } else {
+            (
+                vec![((0., 1.), (uvs[0].x, uvs[1].x))],
+                vec![((0., 1.), (uvs[0].y, uvs[3].y))],
+            )
+        };
+        let to_texture = |uv: Vec2| match self.texture_rect {
+            Some(rect) => (rect.min + uv * source_size) / current_image_size,
+            None => uv,
+        };
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (y, v) in &rows {
+            for (x, u) in &columns {
+                let min = (Vec2::new(x.0, y.0) - 0.5) * quad_size;
+                let max = (Vec2::new(x.1, y.1) - 0.5) * quad_size;
+                let mut geometry = ShapeGeometry::from_points(
+                    QUAD_VERTEX_POSITIONS
+                        .map(|corner| min + (corner + 0.5) * (max - min))
+                        .to_vec(),
+                    QUAD_INDICES.to_vec(),
+                );
+                if let Some(gradient) = &self.gradient {
+                    // The gradient spans the whole sprite, not just this repeat
+                    geometry.min = -quad_size / 2.;
+                    geometry.max = quad_size / 2.;
+                    gradient.apply(&mut geometry, &transform);
+                }
+
+                let (uv_min, uv_max) = (Vec2::new(u.0, v.0), Vec2::new(u.1, v.1));
+                let size = max - min;
+                let size = Vec2::select(size.cmpeq(Vec2::ZERO), Vec2::ONE, size);
+                let vertex_offset = vertices.len() as u32;
+                vertices.extend(geometry.points.iter().enumerate().map(|(i, point)| {
+                    let position = transform
+                        .transform_point((*point - self.anchor.as_vec() * quad_size).extend(0.))
+                        .into();
+                    // Points added by a gradient are in between the corners, so their uvs are too
+                    let uv = to_texture(uv_min + (uv_max - uv_min) * (*point - min) / size);
+                    let color = geometry
+                        .colors
+                        .get(i)
+                        .map_or(self.color, |color| (*color).into());
+
+                    Vertex(BTreeMap::from([
+                        (MeshAttribute::Position, AttributeValue::Position(position)),
+                        (MeshAttribute::UV, AttributeValue::UV(uv.into())),
+                        (MeshAttribute::Color, AttributeValue::Color(color)),
+                    ]))
+                }));
+                indices.extend(geometry.indices.iter().map(|index| index + vertex_offset));
+            }
+        }
 
         let material_handle = self.material.unwrap_or(rb.material_map.default);
 
         let mut mesh = MeshBuilder::new()
-            .with_indices(&QUAD_INDICES)
+            .with_indices(&indices)
             .with_vertices(vertices)
             .with_material(material_handle)
             .with_texture(self.handle)
@@ -156,3 +243,84 @@ impl Anchor {
         }
     }
 }
+
+/// Splits the uvs from `start` to `end` along one axis of a sprite wherever they cross into the next repeat
+/// Returns the position ranges relative to the sprite with the uv ranges inside their repeat, from 0 to 1
+fn tile_segments(start: f32, end: f32) -> Vec<((f32, f32), (f32, f32))> {
+    let mut positions = vec![0., 1.];
+    if start != end {
+        let mut edge = start.min(end).floor() + 1.;
+        while edge < start.max(end) {
+            positions.push((edge - start) / (end - start));
+            edge += 1.;
+        }
+    }
+    positions.sort_by(f32::total_cmp);
+
+    let uv_at = |position: f32| start + (end - start) * position;
+    positions
+        .windows(2)
+        .map(|pair| {
+            let repeat = uv_at((pair[0] + pair[1]) / 2.).floor();
+            (
+                (pair[0], pair[1]),
+                (uv_at(pair[0]) - repeat, uv_at(pair[1]) - repeat),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cut_off_the_last_repeat() {
+        assert_eq!(
+            tile_segments(0., 2.5),
+            vec![
+                ((0., 0.4), (0., 1.)),
+                ((0.4, 0.8), (0., 1.)),
+                ((0.8, 1.), (0., 0.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn offset_tiles_start_inside_a_repeat() {
+        assert_eq!(
+            tile_segments(0.5, 2.5),
+            vec![
+                ((0., 0.25), (0.5, 1.)),
+                ((0.25, 0.75), (0., 1.)),
+                ((0.75, 1.), (0., 0.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn flipped_tiles_go_backwards() {
+        assert_eq!(
+            tile_segments(2., 0.),
+            vec![((0., 0.5), (1., 0.)), ((0.5, 1.), (1., 0.))]
+        );
+    }
+
+    #[test]
+    fn tiled_uvs_stay_inside_their_repeat() {
+        for (start, end) in [(-1.3, 4.1), (3.7, -0.2), (0.25, 0.75), (1., 1.)] {
+            let segments = tile_segments(start, end);
+            assert_eq!(segments.first().unwrap().0 .0, 0.);
+            assert_eq!(segments.last().unwrap().0 .1, 1.);
+            for ((from, to), (uv_from, uv_to)) in segments {
+                assert!(from <= to);
+                for uv in [uv_from, uv_to] {
+                    assert!(
+                        (-1e-5..=1. + 1e-5).contains(&uv),
+                        "{uv} outside of its repeat"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use wgpu::{CompareFunction, StencilFaceState, StencilOperation, StencilState, TextureFormat};
+
+use crate::RenderBuddy;
+
+/// How a mesh interacts with the stencil buffer
+/// Requires a RenderBuddy created with [`RenderBuddyDescriptor::with_stencil`](crate::descriptor::RenderBuddyDescriptor::with_stencil),
+/// without it masks are not drawn and masked meshes are drawn unmasked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum StencilMode {
+    /// Ignores the stencil buffer
+    #[default]
+    Disabled,
+    /// Writes the stencil reference wherever the mesh covers, without drawing any color
+    /// Transparent pixels of meshes using the default material are left out of the mask
+    Mask,
+    /// Only draws where a mask with the same reference was drawn
+    Inside,
+    /// Only draws where no mask with the same reference was drawn
+    Outside,
+}
+
+impl StencilMode {
+    pub(crate) fn stencil_state(&self) -> StencilState {
+        let (compare, pass_op, write_mask) = match self {
+            StencilMode::Disabled => return StencilState::default(),
+            StencilMode::Mask => (CompareFunction::Always, StencilOperation::Replace, !0),
+            StencilMode::Inside => (CompareFunction::Equal, StencilOperation::Keep, 0),
+            StencilMode::Outside => (CompareFunction::NotEqual, StencilOperation::Keep, 0),
+        };
+        let face = StencilFaceState {
+            compare,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op,
+        };
+
+        StencilState {
+            front: face,
+            back: face,
+            read_mask: !0,
+            write_mask,
+        }
+    }
+}
+
+impl RenderBuddy {
+    /// Sets the stencil mode and reference used by every mesh pushed after this
+    /// Meshes with their own stencil mode keep it
+    ///
+    /// Draw the masks with [`StencilMode::Mask`], then the masked meshes with
+    /// [`StencilMode::Inside`] or [`StencilMode::Outside`] and the same reference
+    /// Masks have to be drawn first, so push them before or at a lower z than what they mask
+    pub fn set_stencil(&mut self, stencil_mode: StencilMode, stencil_reference: u32) {
+        self.stencil = (stencil_mode, stencil_reference);
+    }
+}
+
+pub(crate) fn has_stencil(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Stencil8
+            | TextureFormat::Depth24PlusStencil8
+            | TextureFormat::Depth32FloatStencil8
+    )
+}
@@ -1,7 +1,8 @@
 use std::collections::BTreeMap;
 
 use fontdue::layout::{
-    CoordinateSystem, HorizontalAlign, Layout, LayoutSettings, TextStyle, VerticalAlign,
+    CoordinateSystem, GlyphPosition, HorizontalAlign, Layout, LayoutSettings, TextStyle,
+    VerticalAlign, WrapStyle,
 };
 use glam::{Vec2, Vec4};
 use wgpu::TextureFormat;
@@ -10,26 +11,68 @@ use crate::{
     arena::{ArenaId, Handle},
     float_ord::FloatOrd,
     font_atlas::FontAtlas,
-    fonts::{Font, GlyphAtlasInfo, PositionedGlyph},
+    fonts::{Font, GlyphAtlasInfo, GlyphAtlasKey, PositionedGlyph},
     mesh::{
         AttributeValue, BatchMeshCreator, Mesh, MeshAttribute, Vertex, QUAD_INDICES,
         QUAD_VERTEX_POSITIONS,
     },
     pipeline::Pipeline,
-    texture::{Image, Texture},
+    sdf::{SDF_FONT_SIZE, SDF_SPREAD},
+    stencil::StencilMode,
     transform::Transform,
     RenderBuddy,
 };
 
+/// Where lines are placed horizontally inside the bounds of a text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HorizontalAlignment {
+    Left,
+    #[default]
+    Center,
+    Right,
+    /// Widens the spaces so lines fill the bounds, except for the last line of each paragraph
+    Justify,
+}
+
+/// Where the lines are placed vertically inside the bounds of a text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlignment {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+/// Where lines are broken when they don't fit in the width of the bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    /// Breaks between words, words longer than a line are broken between characters
+    #[default]
+    Word,
+    /// Breaks between any two characters
+    Character,
+    /// Only breaks on new lines
+    None,
+}
+
 pub struct Text {
     handle: Handle<Font>,
     material: Option<Handle<Pipeline>>,
     value: String,
     font_size: f32,
     color: Vec4,
-    vertical_alignment: VerticalAlign,
-    horizontal_alignment: HorizontalAlign,
+    bounds: Option<Vec2>,
+    vertical_alignment: VerticalAlignment,
+    horizontal_alignment: HorizontalAlignment,
+    line_height: f32,
+    wrap_mode: WrapMode,
     y_axis_orientation: CoordinateSystem,
+    sdf: bool,
+    outline_width: f32,
+    outline_color: Vec4,
+    shadow_offset: Vec2,
+    shadow_softness: f32,
+    shadow_color: Vec4,
 }
 
 impl Text {
@@ -45,60 +88,263 @@ impl Text {
         self.color = color;
         self
     }
-}
 
-impl RenderBuddy {
-    pub fn measure_text(&mut self, text: &Text) -> Vec2 {
-        let positioned_glyphs = self.get_positioned_glyphs(text, None);
+    /// Lays the text out inside a box of this size, centered on the position of the transform
+    /// Lines wider than the box are wrapped with the wrap mode, text that still doesn't fit overflows the box
+    pub fn with_bounds(mut self, bounds: Vec2) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
 
-        let size: Vec2 = positioned_glyphs.iter().fold(
-            Vec2::default(),
-            |mut size: Vec2, text_glyph: &PositionedGlyph| {
-                let rect = text_glyph.rect;
-                let glyph_position = text_glyph.position;
+    /// Only used for text with bounds
+    pub fn with_horizontal_alignment(mut self, horizontal_alignment: HorizontalAlignment) -> Self {
+        self.horizontal_alignment = horizontal_alignment;
+        self
+    }
 
-                let x_distance = glyph_position.x - size.x;
-                let y_distance = glyph_position.y + size.y;
-                dbg!(y_distance);
-                let actual_glyph_size = rect.size();
-                size.y = size.y.max(actual_glyph_size.y + y_distance.abs() / 2.);
-                size.x += actual_glyph_size.x + x_distance;
+    /// Only used for text with bounds
+    pub fn with_vertical_alignment(mut self, vertical_alignment: VerticalAlignment) -> Self {
+        self.vertical_alignment = vertical_alignment;
+        self
+    }
 
-                size
-            },
-        );
-        // panic!();
-        size
+    /// Distance between lines as a multiple of the font's line height, 1 by default
+    pub fn with_line_height(mut self, line_height: f32) -> Self {
+        self.line_height = line_height;
+        self
     }
-}
 
-impl BatchMeshCreator for Text {
-    fn build(&self, mut transform: Transform, rb: &mut RenderBuddy) -> Vec<crate::mesh::Mesh> {
-        let positioned_glyphs = rb.get_positioned_glyphs(self, None);
+    pub fn with_wrap_mode(mut self, wrap_mode: WrapMode) -> Self {
+        self.wrap_mode = wrap_mode;
+        self
+    }
 
-        let size = positioned_glyphs.iter().fold(
-            Vec2::default(),
-            |mut size: Vec2, text_glyph: &PositionedGlyph| {
-                let rect = text_glyph.rect;
-                let glyph_position = text_glyph.position;
+    /// Draws the glyphs from distance fields generated once per glyph, so the text stays sharp when scaled
+    /// and text of every size shares one atlas
+    pub fn with_sdf(mut self, sdf: bool) -> Self {
+        self.sdf = sdf;
+        self
+    }
+
+    /// Outlines the glyphs, the width is in pixels at the font size and at most an eighth of the font size
+    /// Only SDF text can be outlined, so this turns it on
+    pub fn with_outline(mut self, width: f32, color: Vec4) -> Self {
+        self.outline_width = width;
+        self.outline_color = color;
+        self.sdf = true;
+        self
+    }
 
-                let x_distance = glyph_position.x - size.x;
-                let actual_glyph_size = rect.size();
-                size.y = size.y.max(actual_glyph_size.y);
-                size.x += actual_glyph_size.x + x_distance;
+    /// Draws a shadow behind the glyphs, blurred over `softness` pixels at the font size
+    /// The offset and softness can't reach further than an eighth of the font size from the glyphs
+    /// Only SDF text can have shadows, so this turns it on
+    pub fn with_shadow(mut self, offset: Vec2, softness: f32, color: Vec4) -> Self {
+        self.shadow_offset = offset;
+        self.shadow_softness = softness;
+        self.shadow_color = color;
+        self.sdf = true;
+        self
+    }
 
-                size
+    /// Lays out the glyphs of the text, returning them with the size of the laid out text
+    fn layout(&self, font: &fontdue::Font) -> (Vec<GlyphPosition>, Vec2) {
+        let mut layout = Layout::new(self.y_axis_orientation);
+        layout.reset(&LayoutSettings {
+            x: 0.0,
+            y: 0.0,
+            max_width: self
+                .bounds
+                .filter(|_| self.wrap_mode != WrapMode::None)
+                .map(|bounds| bounds.x),
+            max_height: self.bounds.map(|bounds| bounds.y),
+            // Lines are aligned horizontally below, fontdue can't justify or align lines that aren't wrapped
+            horizontal_align: HorizontalAlign::Left,
+            vertical_align: match self.vertical_alignment {
+                VerticalAlignment::Top => VerticalAlign::Top,
+                VerticalAlignment::Middle => VerticalAlign::Middle,
+                VerticalAlignment::Bottom => VerticalAlign::Bottom,
             },
-        );
+            line_height: self.line_height,
+            wrap_style: match self.wrap_mode {
+                WrapMode::Character => WrapStyle::Letter,
+                WrapMode::Word | WrapMode::None => WrapStyle::Word,
+            },
+            ..Default::default()
+        });
+        layout.append(&[font], &TextStyle::new(&self.value, self.font_size, 0));
+
+        let mut glyphs = layout.glyphs().clone();
+        let lines = layout.lines().cloned().unwrap_or_default();
+        let mut width = 0f32;
+        for (line_index, line) in lines.iter().enumerate() {
+            let line_glyphs = &mut glyphs[line.glyph_start..=line.glyph_end];
+            let visible = |glyph: &GlyphPosition| !glyph.parent.is_whitespace();
+            let left = line_glyphs
+                .iter()
+                .filter(|glyph| visible(glyph))
+                .map(|glyph| glyph.x)
+                .fold(f32::MAX, f32::min);
+            let right = line_glyphs
+                .iter()
+                .filter(|glyph| visible(glyph))
+                .map(|glyph| glyph.x + glyph.width as f32)
+                .fold(f32::MIN, f32::max);
+            if left > right {
+                continue;
+            }
+
+            // Lines start at 0 before they're aligned
+            let line_width = right;
+            let Some(bounds) = self.bounds else {
+                width = width.max(line_width);
+                continue;
+            };
+
+            let free_space = bounds.x - line_width;
+            let ends_paragraph = line_index + 1 == lines.len()
+                || line_glyphs.iter().any(|glyph| glyph.char_data.is_control());
+            let spaces = line_glyphs
+                .iter()
+                .filter(|glyph| !visible(glyph) && glyph.x > left && glyph.x < right)
+                .count();
+
+            match self.horizontal_alignment {
+                HorizontalAlignment::Left => {}
+                HorizontalAlignment::Center => {
+                    line_glyphs
+                        .iter_mut()
+                        .for_each(|glyph| glyph.x += (free_space / 2.).floor());
+                }
+                HorizontalAlignment::Right => {
+                    line_glyphs
+                        .iter_mut()
+                        .for_each(|glyph| glyph.x += free_space.floor());
+                }
+                HorizontalAlignment::Justify => {
+                    if !ends_paragraph && spaces > 0 && free_space > 0. {
+                        let space_width = free_space / spaces as f32;
+                        let mut spaces_before = 0;
+                        for glyph in line_glyphs.iter_mut() {
+                            if !visible(glyph) && glyph.x > left && glyph.x < right {
+                                spaces_before += 1;
+                            }
+                            glyph.x += (space_width * spaces_before as f32).floor();
+                        }
+                    }
+                }
+            }
+
+            let justified = self.horizontal_alignment == HorizontalAlignment::Justify
+                && !ends_paragraph
+                && spaces > 0;
+            width = width.max(if justified {
+                line_width.max(bounds.x)
+            } else {
+                line_width
+            });
+        }
+
+        (glyphs, Vec2::new(width, layout.height()))
+    }
+
+    pub(crate) fn atlas_key(&self) -> GlyphAtlasKey {
+        if self.sdf {
+            GlyphAtlasKey::Sdf
+        } else {
+            GlyphAtlasKey::Bitmap(FloatOrd(self.font_size))
+        }
+    }
+
+    /// Outline and shadow of SDF text, see `sdf_text.wgsl`
+    fn sdf_attributes(&self, text_glyph: &PositionedGlyph) -> [(MeshAttribute, AttributeValue); 4] {
+        // Pixels at the font size to texels and to distance field values
+        let texel_scale = SDF_FONT_SIZE / self.font_size;
+        let field_scale = texel_scale / (2 * SDF_SPREAD) as f32;
+        let atlas_size = text_glyph.atlas_info.atlas_size;
+        let shadow_offset =
+            Vec2::new(self.shadow_offset.x, -self.shadow_offset.y) * texel_scale / atlas_size;
+        let outline_width = (self.outline_width * field_scale).clamp(0., 0.5);
+        let outline_color = if outline_width > 0. {
+            self.outline_color
+        } else {
+            self.color
+        };
+        let rect = text_glyph.rect;
+        let (uv_min, uv_max) = (rect.min / atlas_size, rect.max / atlas_size);
+
+        [
+            (
+                MeshAttribute::Custom(0),
+                AttributeValue::Custom(
+                    0,
+                    [
+                        outline_width,
+                        self.shadow_softness * field_scale,
+                        shadow_offset.x,
+                        shadow_offset.y,
+                    ],
+                ),
+            ),
+            (
+                MeshAttribute::Custom(1),
+                AttributeValue::Custom(1, outline_color.into()),
+            ),
+            (
+                MeshAttribute::Custom(2),
+                AttributeValue::Custom(2, self.shadow_color.into()),
+            ),
+            (
+                MeshAttribute::Custom(3),
+                AttributeValue::Custom(3, [uv_min.x, uv_min.y, uv_max.x, uv_max.y]),
+            ),
+        ]
+    }
+}
 
-        let offset = Vec2::new(size.x / 2. * transform.scale.x, -size.y * transform.scale.y);
+impl RenderBuddy {
+    /// Size of the text once it's laid out, with its bounds, alignment and wrapping
+    pub fn measure_text(&self, text: &Text) -> Vec2 {
+        self.layout_text(text).1
+    }
+}
+
+impl BatchMeshCreator for Text {
+    fn build(&self, mut transform: Transform, rb: &mut RenderBuddy) -> Vec<crate::mesh::Mesh> {
+        let positioned_glyphs = rb.get_positioned_glyphs(self);
+        // Distance fields extend past the glyphs, which mustn't move the text
+        let padding = if self.sdf {
+            SDF_SPREAD as f32 * self.font_size / SDF_FONT_SIZE
+        } else {
+            0.
+        };
+
+        // Text with bounds is centered on its bounds instead of on its glyphs
+        let offset = match self.bounds {
+            Some(bounds) => Vec2::new(bounds.x / 2., -bounds.y / 2.),
+            None => {
+                let size = positioned_glyphs.iter().fold(
+                    Vec2::default(),
+                    |mut size: Vec2, text_glyph: &PositionedGlyph| {
+                        let glyph_position = text_glyph.position + padding;
+
+                        let x_distance = glyph_position.x - size.x;
+                        let actual_glyph_size = text_glyph.size - padding * 2.;
+                        size.y = size.y.max(actual_glyph_size.y);
+                        size.x += actual_glyph_size.x + x_distance;
+
+                        size
+                    },
+                );
+                Vec2::new(size.x / 2., -size.y)
+            }
+        } * transform.scale.truncate();
         transform.position -= offset.extend(0.);
 
         positioned_glyphs
             .iter()
             .map(|text_glyph| {
                 // let transform = Transform::from_translation(position + text_glyph.position.extend(0.));
-                let mut transform = transform.clone();
+                let mut transform = transform;
                 transform.position = transform.transform_point(text_glyph.position.extend(0.));
                 let current_image_size = text_glyph.atlas_info.atlas_size;
                 // let scale_factor = 1f32;
@@ -117,13 +363,14 @@ impl BatchMeshCreator for Text {
                 let positions: [[f32; 3]; 4] = QUAD_VERTEX_POSITIONS.map(|quad_pos| {
                     transform
                         .transform_point(
-                            ((quad_pos - Vec2::new(-0.5, -0.5)) * rect.size()).extend(0.),
+                            ((quad_pos - Vec2::new(-0.5, -0.5)) * text_glyph.size).extend(0.),
                         )
                         .into()
                 });
 
+                let sdf_attributes = self.sdf.then(|| self.sdf_attributes(text_glyph));
                 for i in 0..QUAD_VERTEX_POSITIONS.len() {
-                    vertices.push(Vertex(BTreeMap::from([
+                    let mut vertex = BTreeMap::from([
                         (
                             MeshAttribute::Position,
                             AttributeValue::Position(positions[i]),
@@ -133,10 +380,17 @@ impl BatchMeshCreator for Text {
                             MeshAttribute::Color,
                             AttributeValue::Color(self.color.into()),
                         ),
-                    ])));
+                    ]);
+                    vertex.extend(sdf_attributes.iter().flatten().copied());
+                    vertices.push(Vertex(vertex));
                 }
 
-                let material_handle = self.material.unwrap_or(rb.material_map.default);
+                let default_material = if self.sdf {
+                    rb.material_map.sdf_text
+                } else {
+                    rb.material_map.default
+                };
+                let material_handle = self.material.unwrap_or(default_material);
 
                 Mesh {
                     texture_handle: Some(text_glyph.atlas_info.texture_handle),
@@ -144,6 +398,9 @@ impl BatchMeshCreator for Text {
                     vertices,
                     indices: QUAD_INDICES.to_vec(),
                     z: transform.position.z,
+                    clip: None,
+                    stencil_mode: StencilMode::Disabled,
+                    stencil_reference: 0,
                 }
             })
             .collect()
@@ -157,81 +414,76 @@ impl Default for Text {
             material: None,
             value: Default::default(),
             font_size: Default::default(),
-            vertical_alignment: VerticalAlign::Top,
-            horizontal_alignment: HorizontalAlign::Center,
+            bounds: None,
+            vertical_alignment: VerticalAlignment::Top,
+            horizontal_alignment: HorizontalAlignment::Center,
+            line_height: 1.,
+            wrap_mode: WrapMode::Word,
             y_axis_orientation: CoordinateSystem::PositiveYUp,
             color: Vec4::new(1., 1., 1., 1.), // White
+            sdf: false,
+            outline_width: 0.,
+            outline_color: Vec4::new(0., 0., 0., 1.),
+            shadow_offset: Vec2::ZERO,
+            shadow_softness: 0.,
+            shadow_color: Vec4::ZERO,
         }
     }
 }
 
 impl RenderBuddy {
-    pub(crate) fn get_positioned_glyphs(
-        &mut self,
-        text: &Text,
-        container_size: Option<Vec2>,
-    ) -> Vec<PositionedGlyph> {
-        let texture = self.add_glyphs_to_atlas(text.handle, &text.value, text.font_size);
-
-        if let Some(temp_texture_data) = texture {
-            let texture = self.add_texture_bytes(
-                &temp_texture_data.data,
-                temp_texture_data.dimensions,
-                crate::texture::TextureSamplerType::Linear,
-                TextureFormat::Rgba8UnormSrgb,
-            );
-
-            // Update texture or insert new texture
-            if let Some(handle) = self
-                .fonts
-                .get(text.handle)
-                .unwrap()
-                .texture_ids
-                .get(&(FloatOrd(text.font_size)))
-            {
-                *self.textures.get_mut(*handle).unwrap() = texture;
-            } else {
-                let texture_handle = self.textures.insert(texture);
-
-                self.fonts
-                    .get_mut(text.handle)
-                    .unwrap()
-                    .texture_ids
-                    .insert(FloatOrd(text.font_size), texture_handle);
-            }
+    pub(crate) fn get_positioned_glyphs(&mut self, text: &Text) -> Vec<PositionedGlyph> {
+        let atlas_key = text.atlas_key();
+        let changed_pages = self.add_glyphs_to_atlas(text.handle, &text.value, atlas_key);
+        for page in changed_pages {
+            self.upload_font_atlas_page(text.handle, atlas_key, page);
         }
-
-        let texture_handle = *self
-            .fonts
-            .get(text.handle)
+        // The meshes get uvs for the current size of the pages, which have to keep it for the rest of the frame
+        let frame = self.frame;
+        self.font_atlases
+            .get_mut(&(atlas_key, text.handle.id))
             .unwrap()
-            .texture_ids
-            .get(&(FloatOrd(text.font_size)))
-            .expect("Error, missing texture id for font");
+            .draw_glyphs(&text.value, frame);
 
         let mut positioned_glyphs = Vec::new();
 
-        let mut layout = Layout::new(text.y_axis_orientation);
-
-        layout.reset(&LayoutSettings {
-            x: 0.0,
-            y: 0.0,
-            max_width: container_size.map(|size| size.x),
-            max_height: container_size.map(|size| size.y),
-            horizontal_align: text.horizontal_alignment,
-            vertical_align: text.vertical_alignment,
-            ..Default::default()
-        });
+        let (glyphs, _) = self.layout_text(text);
         let font = &self.fonts.get(text.handle).unwrap().font;
-        layout.append(&[font], &TextStyle::new(&text.value, text.font_size, 0));
-
-        for glyph in layout.glyphs() {
-            let atlas_info = self
-                .get_glyph_atlas_info(text.font_size, text.handle.id, glyph.parent, texture_handle)
-                .unwrap();
+        // New lines are kept as glyphs at the end of their line
+        for glyph in glyphs.iter().filter(|glyph| !glyph.char_data.is_control()) {
+            // Glyphs too big for any texture aren't in the atlas
+            let Some(atlas_info) =
+                self.get_glyph_atlas_info(atlas_key, text.handle.id, glyph.parent)
+            else {
+                continue;
+            };
+
+            let (position, size) = match atlas_key {
+                GlyphAtlasKey::Bitmap(_) => {
+                    (Vec2::new(glyph.x, glyph.y), atlas_info.texture_rect.size())
+                }
+                GlyphAtlasKey::Sdf => {
+                    // Layout positions are the pen position plus the rounded bounds of the glyph at the font size,
+                    // the field is placed from the pen position so it lines up at any size
+                    let bounds = font.metrics(glyph.parent, text.font_size).bounds;
+                    let pen =
+                        Vec2::new(glyph.x - bounds.xmin.floor(), glyph.y - bounds.ymin.floor());
+                    let scale = text.font_size / SDF_FONT_SIZE;
+                    let field_min = Vec2::new(
+                        atlas_info.metrics.xmin as f32,
+                        atlas_info.metrics.ymin as f32,
+                    );
+
+                    (
+                        pen + field_min * scale,
+                        atlas_info.texture_rect.size() * scale,
+                    )
+                }
+            };
 
             positioned_glyphs.push(PositionedGlyph {
-                position: Vec2::new(glyph.x, glyph.y),
+                position,
+                size,
                 rect: atlas_info.texture_rect,
                 atlas_info,
             });
@@ -240,51 +492,291 @@ impl RenderBuddy {
         positioned_glyphs
     }
 
+    /// Writes the glyphs added to an atlas page into its texture,
+    /// pages that are new or grew get a new texture instead
+    fn upload_font_atlas_page(
+        &mut self,
+        font_handle: Handle<Font>,
+        atlas_key: GlyphAtlasKey,
+        page: usize,
+    ) {
+        let key = (atlas_key, font_handle.id);
+        let dirty_rect = self.font_atlases.get_mut(&key).unwrap().pages[page].take_dirty_rect();
+        let texture_handle = self
+            .fonts
+            .get(font_handle)
+            .unwrap()
+            .texture_ids
+            .get(&atlas_key)
+            .and_then(|pages| pages.get(page))
+            .copied();
+
+        let temp_texture_data = &self.font_atlases[&key].pages[page].temp_image_data;
+        let dimensions = Vec2::new(
+            temp_texture_data.dimensions.0 as f32,
+            temp_texture_data.dimensions.1 as f32,
+        );
+        if let Some(handle) = texture_handle.filter(|handle| {
+            self.textures
+                .get(*handle)
+                .is_some_and(|texture| texture.dimensions == dimensions)
+        }) {
+            if let Some(dirty_rect) = dirty_rect {
+                self.write_texture_region(handle, temp_texture_data, dirty_rect);
+            }
+            return;
+        }
+
+        let temp_texture_data = temp_texture_data.clone();
+        let texture = self
+            .add_texture_bytes(
+                &temp_texture_data.data,
+                temp_texture_data.dimensions,
+                crate::texture::TextureSamplerType::Linear,
+                TextureFormat::Rgba8UnormSrgb,
+            )
+            .expect("Font atlas texture is always valid");
+
+        if let Some(handle) = texture_handle {
+            self.replace_texture(handle, texture);
+        } else {
+            let texture_handle = self.textures.insert(texture);
+
+            self.fonts
+                .get_mut(font_handle)
+                .unwrap()
+                .texture_ids
+                .entry(atlas_key)
+                .or_default()
+                .push(texture_handle);
+        }
+    }
+
+    fn layout_text(&self, text: &Text) -> (Vec<GlyphPosition>, Vec2) {
+        text.layout(&self.fonts.get(text.handle).unwrap().font)
+    }
+
+    /// Rasterizes the characters missing from the atlas, returning the pages they were added to
     pub(crate) fn add_glyphs_to_atlas(
         &mut self,
         font_handle: Handle<Font>,
         text: &str,
-        font_size: f32,
-    ) -> Option<Image> {
+        atlas_key: GlyphAtlasKey,
+    ) -> Vec<usize> {
+        let max_texture_size = self.device.limits().max_texture_dimension_2d;
+        let frame = self.frame;
+        let evict_unused = self.glyph_cache_size() > self.glyph_cache_budget;
         let font_atlas = self
             .font_atlases
-            .entry((FloatOrd(font_size), font_handle.id))
-            .or_insert_with(|| FontAtlas::new(Vec2::splat(512.0)));
+            .entry((atlas_key, font_handle.id))
+            .or_insert_with(|| FontAtlas::new(max_texture_size));
         let font = self.fonts.get(font_handle).unwrap();
-        let mut update_texture_data = None;
+        let mut changed_pages = Vec::new();
         for character in text.chars() {
-            if !font_atlas.has_glyph(character) {
-                let (metrics, bitmap) = font.rasterize(character, font_size);
-                update_texture_data = font_atlas.add_glyph(character, &bitmap, metrics);
+            if !font_atlas.use_glyph(character, frame) {
+                let (metrics, bitmap) = match atlas_key {
+                    GlyphAtlasKey::Bitmap(font_size) => font.rasterize(character, font_size.0),
+                    GlyphAtlasKey::Sdf => font.rasterize_sdf(character),
+                };
+                changed_pages.extend(font_atlas.add_glyph(
+                    character,
+                    &bitmap,
+                    metrics,
+                    frame,
+                    evict_unused,
+                ));
             }
         }
 
-        update_texture_data
+        // Pages are uploaded in order so new pages get their texture after the pages before them
+        changed_pages.sort_unstable();
+        changed_pages.dedup();
+        changed_pages
     }
 
     pub fn get_glyph_atlas_info(
-        &mut self,
-        font_size: f32,
+        &self,
+        atlas_key: GlyphAtlasKey,
         font_id: ArenaId,
         glyph: char,
-        texture_handle: Handle<Texture>,
     ) -> Option<GlyphAtlasInfo> {
-        let key = (FloatOrd(font_size), font_id);
-        let atlas = self.font_atlases.get(&key);
+        let key = (atlas_key, font_id);
+        let atlas_glyph = self.font_atlases.get(&key)?.get_glyph(glyph)?;
+        let texture_atlas = &self.font_atlases[&key].pages[atlas_glyph.page].texture_atlas;
+        let texture_handle = *self
+            .fonts
+            .get(Handle::new(font_id))?
+            .texture_ids
+            .get(&atlas_key)?
+            .get(atlas_glyph.page)?;
+
+        Some(GlyphAtlasInfo {
+            texture_rect: texture_atlas.textures.get(atlas_glyph.index).copied()?,
+            metrics: atlas_glyph.metrics,
+            texture_handle,
+            atlas_size: texture_atlas.size,
+        })
+    }
+}
 
-        if let Some(font_atlas) = atlas {
-            let texture_atlas = &font_atlas.dynamic_texture_atlas_builder.texture_atlas;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            return font_atlas
-                .get_glyph_index(glyph)
-                .map(|(glyph_index, metrics)| GlyphAtlasInfo {
-                    texture_rect: texture_atlas.textures.get(glyph_index).copied().unwrap(),
-                    metrics,
-                    texture_handle,
-                    atlas_size: texture_atlas.size,
-                });
+    const FONT_SIZE: f32 = 20.;
+
+    fn font() -> fontdue::Font {
+        fontdue::Font::from_bytes(
+            include_bytes!("default_font/Roboto-Regular.ttf") as &[u8],
+            fontdue::FontSettings::default(),
+        )
+        .unwrap()
+    }
+
+    /// Left and right edge of the visible glyphs of each line, from the top
+    fn line_edges(glyphs: &[GlyphPosition]) -> Vec<(f32, f32)> {
+        let mut lines: Vec<(f32, f32, f32)> = Vec::new();
+        for glyph in glyphs.iter().filter(|glyph| !glyph.parent.is_whitespace()) {
+            // Glyphs of a line share a baseline, but not the top of their bounds
+            let baseline = glyph.y + glyph.height as f32;
+            let right = glyph.x + glyph.width as f32;
+            match lines
+                .iter_mut()
+                .find(|line| (line.0 - baseline).abs() < FONT_SIZE / 2.)
+            {
+                Some(line) => {
+                    line.1 = line.1.min(glyph.x);
+                    line.2 = line.2.max(right);
+                }
+                None => lines.push((baseline, glyph.x, right)),
+            }
         }
+        lines.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        lines.into_iter().map(|line| (line.1, line.2)).collect()
+    }
+
+    #[test]
+    fn wraps_words_inside_the_bounds() {
+        let text = Text::new("the quick brown fox jumps over", FONT_SIZE)
+            .with_bounds(Vec2::new(100., 200.))
+            .with_horizontal_alignment(HorizontalAlignment::Left);
+        let (glyphs, size) = text.layout(&font());
+
+        let lines = line_edges(&glyphs);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|(_, right)| *right <= 100.));
+        assert!(size.x <= 100.);
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let layout = |value, wrap_mode| {
+            let text = Text::new(value, FONT_SIZE)
+                .with_bounds(Vec2::new(100., 200.))
+                .with_horizontal_alignment(HorizontalAlignment::Left)
+                .with_wrap_mode(wrap_mode);
+            let (glyphs, size) = text.layout(&font());
+            (line_edges(&glyphs), size)
+        };
+
+        // Word wrapping moves the whole second word to the next line
+        let (word_lines, _) = layout("ab cdefghijkl", WrapMode::Word);
+        let (character_lines, _) = layout("ab cdefghijkl", WrapMode::Character);
+        assert_eq!(word_lines.len(), 2);
+        assert_eq!(character_lines.len(), 2);
+        assert!(word_lines[0].1 < FONT_SIZE * 2.);
+        assert!(character_lines[0].1 > word_lines[0].1 + FONT_SIZE);
+
+        // Words longer than a line are still broken
+        let (lines, size) = layout("abcdefghijklmnopqrstuvwxyz", WrapMode::Word);
+        assert!(lines.len() > 1);
+        assert!(size.x <= 100.);
+
+        let (lines, size) = layout(
+            "the quick brown fox jumps over\nthe lazy dog",
+            WrapMode::None,
+        );
+        assert_eq!(lines.len(), 2);
+        assert!(size.x > 100.);
+    }
+
+    #[test]
+    fn aligns_lines_inside_the_bounds() {
+        let layout = |alignment| {
+            let text = Text::new("the quick brown fox jumps over", FONT_SIZE)
+                .with_bounds(Vec2::new(120., 200.))
+                .with_horizontal_alignment(alignment);
+            line_edges(&text.layout(&font()).0)
+        };
+
+        for (left, right) in layout(HorizontalAlignment::Left) {
+            assert!(left < 2.);
+            assert!(right <= 120.);
+        }
+        for (left, right) in layout(HorizontalAlignment::Right) {
+            assert!((120. - right).abs() <= 1.);
+            assert!(left > 0.);
+        }
+        for (left, right) in layout(HorizontalAlignment::Center) {
+            assert!((left - (120. - right)).abs() <= 3.);
+        }
+    }
+
+    #[test]
+    fn justify_fills_all_but_the_last_line_of_a_paragraph() {
+        let text = Text::new(
+            "the quick brown fox jumps over\nthe lazy dog and the quick brown fox",
+            FONT_SIZE,
+        )
+        .with_bounds(Vec2::new(150., 300.))
+        .with_horizontal_alignment(HorizontalAlignment::Justify);
+        let (glyphs, size) = text.layout(&font());
+        let lines = line_edges(&glyphs);
+        assert!(lines.len() >= 4);
+
+        let justified = lines
+            .iter()
+            .filter(|(_, right)| (150. - right).abs() <= 1.)
+            .count();
+        let unjustified = lines.iter().filter(|(_, right)| *right < 140.).count();
+        // One line ends the first paragraph and one ends the text
+        assert_eq!(justified, lines.len() - 2);
+        assert_eq!(unjustified, 2);
+        assert_eq!(size.x, 150.);
+    }
+
+    #[test]
+    fn line_height_spreads_the_lines() {
+        let layout = |line_height| {
+            let text = Text::new("one\ntwo\nthree", FONT_SIZE).with_line_height(line_height);
+            let (glyphs, size) = text.layout(&font());
+            (line_edges(&glyphs).len(), size.y)
+        };
+
+        let (lines, height) = layout(1.);
+        let (double_lines, double_height) = layout(2.);
+        assert_eq!(lines, 3);
+        assert_eq!(double_lines, 3);
+        // The line height is the space to the next line, the last line keeps its own height
+        let line_size = height / 3.;
+        assert!((double_height - (height + line_size * 2.)).abs() <= 1.);
+    }
 
-        None
+    #[test]
+    fn measures_text_without_bounds() {
+        let text = Text::new("hello\nwide world", FONT_SIZE);
+        let (glyphs, size) = text.layout(&font());
+
+        let lines = line_edges(&glyphs);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(size.x, lines[1].1);
+        assert!(size.y >= FONT_SIZE * 2.);
+        // Bounds don't change the width of text that fits in them
+        let (_, bounded_size) = Text::new("hello\nwide world", FONT_SIZE)
+            .with_bounds(Vec2::new(500., 500.))
+            .with_horizontal_alignment(HorizontalAlignment::Left)
+            .layout(&font());
+        assert_eq!(bounded_size.x, size.x);
     }
 }
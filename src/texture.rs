@@ -1,14 +1,12 @@
 use glam::Vec2;
-use std::{num::NonZeroU32, sync::Arc};
+use std::num::NonZeroU32;
 use wgpu::{
-    BindGroup, BindGroupLayout, BindingResource, Device, Extent3d, Queue, Sampler,
-    SurfaceConfiguration, TextureFormat,
+    AddressMode, BindGroup, BindGroupLayout, BindingResource, Device, Extent3d, FilterMode, Queue,
+    Sampler, TextureFormat,
 };
 
 use crate::{
-    arena::{Arena, ArenaId, Handle},
-    bind_groups::BindGroupBuilder,
-    RenderBuddy,
+    arena::Handle, bind_groups::BindGroupBuilder, errors::RenderBuddyError, rect::Rect, RenderBuddy,
 };
 
 #[derive(Default, Debug, PartialEq, Hash, Eq, Clone, Copy)]
@@ -17,6 +15,44 @@ pub enum TextureSamplerType {
     #[default]
     Nearest,
     Depth,
+    /// Repeats the texture outside of the 0 to 1 uv range, filtered with the given mode
+    Repeat(FilterMode),
+    /// Repeats the texture outside of the 0 to 1 uv range, mirroring every other repeat
+    MirrorRepeat(FilterMode),
+}
+
+impl TextureSamplerType {
+    /// Every sampler type that has a default sampler
+    pub(crate) const ALL: [TextureSamplerType; 7] = [
+        TextureSamplerType::Linear,
+        TextureSamplerType::Nearest,
+        TextureSamplerType::Depth,
+        TextureSamplerType::Repeat(FilterMode::Linear),
+        TextureSamplerType::Repeat(FilterMode::Nearest),
+        TextureSamplerType::MirrorRepeat(FilterMode::Linear),
+        TextureSamplerType::MirrorRepeat(FilterMode::Nearest),
+    ];
+
+    pub(crate) fn create_sampler(&self, device: &Device) -> Sampler {
+        let (filter, address_mode) = match *self {
+            TextureSamplerType::Linear => (FilterMode::Linear, AddressMode::ClampToEdge),
+            TextureSamplerType::Nearest | TextureSamplerType::Depth => {
+                (FilterMode::Nearest, AddressMode::ClampToEdge)
+            }
+            TextureSamplerType::Repeat(filter) => (filter, AddressMode::Repeat),
+            TextureSamplerType::MirrorRepeat(filter) => (filter, AddressMode::MirrorRepeat),
+        };
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter: filter,
+            ..Default::default()
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -81,14 +117,12 @@ impl Texture {
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        let texture = Texture {
+        Texture {
             texture,
             view,
             dimensions: Vec2::new(size.width as f32, size.height as f32),
             sampler,
-        };
-
-        texture
+        }
     }
 
     pub fn create_bind_group(
@@ -99,30 +133,105 @@ impl Texture {
     ) -> BindGroup {
         BindGroupBuilder::new()
             .append_texture_view(&self.view)
-            .append(BindingResource::Sampler(&sampler))
-            .build(device, None, &bgl)
+            .append(BindingResource::Sampler(sampler))
+            .build(device, None, bgl)
+    }
+
+    pub(crate) fn create_render_target(
+        device: &Device,
+        size: (u32, u32),
+        format: TextureFormat,
+        sampler: Handle<Sampler>,
+    ) -> Self {
+        let size = Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Render target texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Texture {
+            texture,
+            view,
+            dimensions: Vec2::new(size.width as f32, size.height as f32),
+            sampler,
+        }
+    }
+
+    /// Creates the multisampled color texture that is rendered into and resolved to the surface
+    pub(crate) fn create_multisampled_texture(
+        device: &Device,
+        size: (u32, u32),
+        format: TextureFormat,
+        sample_count: u32,
+        sampler: Handle<Sampler>,
+    ) -> Self {
+        let size = Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Multisampled texture"),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Texture {
+            texture,
+            view,
+            dimensions: Vec2::new(size.width as f32, size.height as f32),
+            sampler,
+        }
     }
 
     pub(crate) fn create_depth_texture(
         device: &Device,
-        surface_config: &SurfaceConfiguration,
+        size: (u32, u32),
+        format: TextureFormat,
+        sample_count: u32,
         sampler: Handle<Sampler>,
     ) -> Self {
         let size = wgpu::Extent3d {
             // 2.
-            width: surface_config.width,
-            height: surface_config.height,
+            width: size.0,
+            height: size.1,
             depth_or_array_layers: 1,
         };
         let desc = wgpu::TextureDescriptor {
             label: Some("Depth texture"),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
-            format: TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[TextureFormat::Depth32Float],
+            format,
+            // Multisampled depth stencil textures can't be bound on every backend
+            usage: if sample_count > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING
+            },
+            view_formats: &[format],
         };
         let texture = device.create_texture(&desc);
 
@@ -132,7 +241,7 @@ impl Texture {
         Texture {
             texture,
             view,
-            dimensions: Vec2::new(surface_config.width as f32, surface_config.height as f32),
+            dimensions: Vec2::new(size.width as f32, size.height as f32),
             sampler,
         }
     }
@@ -141,7 +250,7 @@ impl Texture {
 impl RenderBuddy {
     /// Loads a texture to the GPU
     /// Returns a handle to the texture ref
-    pub fn add_texture(&mut self, image: Image) -> Handle<Texture> {
+    pub fn add_texture(&mut self, image: Image) -> Result<Handle<Texture>, RenderBuddyError> {
         self.add_texture_from_bytes(&image.data, image.dimensions, image.sampler, image.format)
     }
 
@@ -153,9 +262,9 @@ impl RenderBuddy {
         size: (u32, u32),
         sampler: TextureSamplerType,
         format: TextureFormat,
-    ) -> Handle<Texture> {
-        let texture = self.add_texture_bytes(bytes, size, sampler, format);
-        self.textures.insert(texture)
+    ) -> Result<Handle<Texture>, RenderBuddyError> {
+        let texture = self.add_texture_bytes(bytes, size, sampler, format)?;
+        Ok(self.textures.insert(texture))
     }
 
     pub(crate) fn add_texture_bytes(
@@ -164,7 +273,37 @@ impl RenderBuddy {
         size: (u32, u32),
         texture_sampler_type: TextureSamplerType,
         format: TextureFormat,
-    ) -> Texture {
+    ) -> Result<Texture, RenderBuddyError> {
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        if size.0 == 0 || size.1 == 0 || size.0 > max_dimension || size.1 > max_dimension {
+            return Err(RenderBuddyError::InvalidTextureSize {
+                width: size.0,
+                height: size.1,
+            });
+        }
+
+        let format_described = format.describe();
+        if format_described.is_compressed()
+            || !self
+                .device
+                .features()
+                .contains(format_described.required_features)
+            || !format_described
+                .guaranteed_format_features
+                .allowed_usages
+                .contains(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST)
+        {
+            return Err(RenderBuddyError::UnsupportedTextureFormat(format));
+        }
+
+        let expected_len = format_described.block_size as usize * size.0 as usize * size.1 as usize;
+        if bytes.len() != expected_len {
+            return Err(RenderBuddyError::InvalidTextureData {
+                expected: expected_len,
+                actual: bytes.len(),
+            });
+        }
+
         let size = Extent3d {
             width: size.0 as _,
             height: size.1 as _,
@@ -182,7 +321,6 @@ impl RenderBuddy {
             view_formats: &[],
         };
 
-        let format_described = texture_descriptor.format.describe();
         let texture = self.device.create_texture(&texture_descriptor);
 
         self.queue.write_texture(
@@ -203,7 +341,7 @@ impl RenderBuddy {
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        Texture {
+        Ok(Texture {
             texture,
             view,
             dimensions: Vec2::new(size.width as f32, size.height as f32),
@@ -211,21 +349,179 @@ impl RenderBuddy {
                 .default_texture_samplers
                 .get(&texture_sampler_type)
                 .unwrap(),
-        }
+        })
+    }
+
+    /// Creates a texture that can be rendered into with [`RenderBuddy::render_to_target`]
+    /// and drawn like any other texture afterwards
+    pub fn create_render_target(
+        &mut self,
+        size: (u32, u32),
+        format: TextureFormat,
+    ) -> Handle<Texture> {
+        let render_target = Texture::create_render_target(
+            &self.device,
+            size,
+            format,
+            *self
+                .default_texture_samplers
+                .get(&TextureSamplerType::Linear)
+                .unwrap(),
+        );
+        let depth_texture = Texture::create_depth_texture(
+            &self.device,
+            size,
+            self.depth_format,
+            1,
+            *self
+                .default_texture_samplers
+                .get(&TextureSamplerType::Depth)
+                .unwrap(),
+        );
+
+        let handle = self.textures.insert(render_target);
+        let depth_texture_handle = self.textures.insert(depth_texture);
+        self.render_target_depth_textures
+            .insert(handle.id, depth_texture_handle);
+
+        handle
     }
 
     pub(crate) fn replace_texture(&mut self, handle: Handle<Texture>, texture: Texture) {
+        self.texture_bind_groups
+            .retain(|key, _| key.texture != handle.id);
         *self
             .textures
             .get_mut(handle)
             .expect("No texture to replace") = texture;
     }
 
+    /// Writes part of an image of the same size and format as the texture into it
+    pub(crate) fn write_texture_region(
+        &self,
+        handle: Handle<Texture>,
+        image: &Image,
+        region: Rect,
+    ) {
+        let texture = self.textures.get(handle).expect("No texture to write to");
+        let block_size = image.format.describe().block_size as u32;
+        let (min, size) = (region.min.as_uvec2(), region.size().as_uvec2());
+
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: min.x,
+                    y: min.y,
+                    z: 0,
+                },
+            },
+            &image.data,
+            wgpu::ImageDataLayout {
+                offset: ((min.y * image.dimensions.0 + min.x) * block_size) as u64,
+                bytes_per_row: NonZeroU32::new(image.dimensions.0 * block_size),
+                rows_per_image: NonZeroU32::new(size.y),
+            },
+            Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    pub(crate) fn remove_texture(&mut self, handle: Handle<Texture>) {
+        self.texture_bind_groups
+            .retain(|key, _| key.texture != handle.id);
+        self.textures.remove(handle);
+    }
+
     /// Replaces the given texture handle
     /// Useful for hot reloading
-    pub fn replace_image(&mut self, handle: Handle<Texture>, image: Image) {
+    pub fn replace_image(
+        &mut self,
+        handle: Handle<Texture>,
+        image: Image,
+    ) -> Result<(), RenderBuddyError> {
+        if !self.textures.contains(handle) {
+            return Err(RenderBuddyError::InvalidHandle("texture"));
+        }
+
         let texture: Texture =
-            self.add_texture_bytes(&image.data, image.dimensions, image.sampler, image.format);
-        self.replace_texture(handle, texture)
+            self.add_texture_bytes(&image.data, image.dimensions, image.sampler, image.format)?;
+        self.replace_texture(handle, texture);
+
+        Ok(())
+    }
+
+    /// Copies a texture back from the GPU, blocks until the copy has finished
+    /// Row padding required by wgpu is stripped from the returned [`Image`]
+    pub(crate) fn read_texture(&self, texture: &wgpu::Texture) -> Result<Image, RenderBuddyError> {
+        let size = texture.size();
+        let format = texture.format();
+
+        let unpadded_bytes_per_row = format.describe().block_size as u32 * size.width;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(alignment) * alignment;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture readback buffer"),
+            size: padded_bytes_per_row as u64 * size.height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut command_encoder =
+            self.device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("Texture readback encoder"),
+                });
+        command_encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(size.height),
+                },
+            },
+            size,
+        );
+        self.queue.submit(std::iter::once(command_encoder.finish()));
+
+        let buffer_slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+
+        match receiver.recv() {
+            Ok(result) => result?,
+            Err(_) => return Err(RenderBuddyError::BufferMap(wgpu::BufferAsyncError)),
+        }
+
+        let data = buffer_slice
+            .get_mapped_range()
+            .chunks(padded_bytes_per_row as usize)
+            .flat_map(|row| &row[..unpadded_bytes_per_row as usize])
+            .copied()
+            .collect();
+        buffer.unmap();
+
+        Ok(Image {
+            data,
+            dimensions: (size.width, size.height),
+            format,
+            ..Default::default()
+        })
     }
 }